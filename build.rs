@@ -0,0 +1,39 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// With the `embed-objects` feature, generate the list of objects to embed
+/// from the files in `ARGUS_EMBED_DIR`, see `src/embedded.rs`.
+fn main() {
+    println!("cargo:rerun-if-env-changed=ARGUS_EMBED_DIR");
+    if env::var_os("CARGO_FEATURE_EMBED_OBJECTS").is_none() {
+        return;
+    }
+
+    let dir = env::var("ARGUS_EMBED_DIR")
+        .expect("the embed-objects feature needs ARGUS_EMBED_DIR, the directory of the objects");
+    let dir = fs::canonicalize(&dir).unwrap_or_else(|e| panic!("ARGUS_EMBED_DIR={}: {}", dir, e));
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut objects: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    objects.sort();
+
+    let mut code = String::from("pub static EMBEDDED_OBJECTS: &[(&str, &[u8])] = &[\n");
+    for object in &objects {
+        println!("cargo:rerun-if-changed={}", object.display());
+        let name = object.file_name().unwrap().to_string_lossy();
+        code.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            name,
+            object.display().to_string()
+        ));
+    }
+    code.push_str("];\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded_objects.rs");
+    fs::write(&out, code).unwrap_or_else(|e| panic!("{}: {}", out.display(), e));
+}
@@ -0,0 +1,122 @@
+use crate::compiler_option::CompilerOption;
+
+/// What a compiler invocation produces, i.e. the last phase the driver runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileMode {
+    /// `-E`: preprocess only.
+    Preprocess,
+    /// `-M` / `-MM`: only write the dependencies.
+    DepsOnly,
+    /// `-fsyntax-only`: parse and check, produce nothing.
+    SyntaxOnly,
+    /// `-S`: compile to assembly.
+    Assemble,
+    /// `-c`: compile to object files.
+    CompileOnly,
+    /// Link an executable.
+    LinkExecutable,
+    /// `-shared`: link a shared library.
+    LinkShared,
+    /// `-r`: partial link into a relocatable object.
+    LinkRelocatable,
+    /// Nothing is compiled, e.g. `--version`, `-###`, `-print-*` or no inputs at all.
+    Query,
+}
+
+const QUERY_FLAGS: &[&str] = &[
+    "--version",
+    "--help",
+    "-###",
+    "-dumpmachine",
+    "-dumpversion",
+    "-print-libgcc-file-name",
+    "-print-multi-directory",
+    "-print-multi-lib",
+    "-print-resource-dir",
+    "-print-search-dirs",
+    "-print-target-triple",
+    "-print-effective-triple",
+];
+
+impl CompileMode {
+    /// Classify a command. Like the clang driver, the earliest stopping phase
+    /// wins, e.g. `-c -E` only preprocesses.
+    pub fn from_options(options: &[CompilerOption]) -> Self {
+        let has = |name: &str| options.iter().any(|opt| opt.is_enabled && opt.name == name);
+        let is_query = options.iter().any(|opt| {
+            opt.is_enabled
+                && (QUERY_FLAGS.contains(&opt.name.as_str())
+                    || opt.name.starts_with("-print-prog-name=")
+                    || opt.name.starts_with("-print-file-name="))
+        });
+
+        if is_query || !options.iter().any(|opt| opt.is_enabled && opt.is_input()) {
+            CompileMode::Query
+        } else if has("-M") || has("-MM") {
+            CompileMode::DepsOnly
+        } else if has("-E") {
+            CompileMode::Preprocess
+        } else if has("-fsyntax-only") {
+            CompileMode::SyntaxOnly
+        } else if has("-S") {
+            CompileMode::Assemble
+        } else if has("-c") {
+            CompileMode::CompileOnly
+        } else if has("-r") {
+            CompileMode::LinkRelocatable
+        } else if has("-shared") {
+            CompileMode::LinkShared
+        } else {
+            CompileMode::LinkExecutable
+        }
+    }
+
+    /// Whether the linker runs.
+    pub fn is_link(&self) -> bool {
+        matches!(
+            self,
+            CompileMode::LinkExecutable | CompileMode::LinkShared | CompileMode::LinkRelocatable
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_table::parse_args;
+
+    fn mode(args: &[&str]) -> CompileMode {
+        CompileMode::from_options(&parse_args(args))
+    }
+
+    #[test]
+    fn stopping_phases() {
+        assert_eq!(mode(&["-E", "a.c"]), CompileMode::Preprocess);
+        assert_eq!(mode(&["-MM", "a.c"]), CompileMode::DepsOnly);
+        assert_eq!(mode(&["-fsyntax-only", "a.c"]), CompileMode::SyntaxOnly);
+        assert_eq!(mode(&["-S", "a.c"]), CompileMode::Assemble);
+        assert_eq!(mode(&["-c", "a.c", "-o", "a.o"]), CompileMode::CompileOnly);
+        // the earliest phase wins
+        assert_eq!(mode(&["-c", "-E", "a.c"]), CompileMode::Preprocess);
+        assert_eq!(mode(&["-S", "-c", "a.c"]), CompileMode::Assemble);
+    }
+
+    #[test]
+    fn link_modes() {
+        assert_eq!(mode(&["a.o", "-o", "a.out"]), CompileMode::LinkExecutable);
+        assert_eq!(mode(&["a.c"]), CompileMode::LinkExecutable);
+        assert_eq!(mode(&["-shared", "-fPIC", "a.o"]), CompileMode::LinkShared);
+        assert_eq!(mode(&["-r", "a.o", "b.o"]), CompileMode::LinkRelocatable);
+        assert_eq!(mode(&["-c", "-shared", "a.c"]), CompileMode::CompileOnly);
+        assert!(mode(&["-shared", "a.o"]).is_link());
+        assert!(!mode(&["-c", "a.c"]).is_link());
+    }
+
+    #[test]
+    fn queries() {
+        assert_eq!(mode(&["--version"]), CompileMode::Query);
+        assert_eq!(mode(&["-###", "-c", "a.c"]), CompileMode::Query);
+        assert_eq!(mode(&["-print-file-name=libc.so"]), CompileMode::Query);
+        assert_eq!(mode(&["-c", "-O2"]), CompileMode::Query);
+    }
+}
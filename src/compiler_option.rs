@@ -1,9 +1,21 @@
+use crate::{
+    compile_mode::CompileMode,
+    error::ArgusError,
+    input_file::{collect_inputs, InputFile},
+    option_table::{self, OptionKind, ValueGrammar},
+    provenance::Provenance,
+};
+
+/// A single logical option. `name` is the spelling as found in the option
+/// table (e.g. `-o`, `-fsanitize=`, `-Wl,`), `values` holds its argument(s).
 #[derive(Debug, Clone)]
 pub struct CompilerOption {
     pub name: String,
     pub values: Vec<String>,
+    pub kind: OptionKind,
     pub is_enabled: bool,
     pub has_value: bool,
+    pub provenance: Provenance,
 }
 
 impl CompilerOption {
@@ -11,34 +23,69 @@ impl CompilerOption {
         CompilerOption {
             name: name.to_string(),
             values: Vec::new(),
+            kind: OptionKind::Flag,
             is_enabled: true,
             has_value: false,
+            provenance: Provenance::default(),
         }
     }
 
+    pub fn new_input(path: &str) -> Self {
+        CompilerOption {
+            kind: OptionKind::Input,
+            ..CompilerOption::new(path)
+        }
+    }
+
+    /// Create an option with values. The kind is taken from the option table,
+    /// so `new_with_value("-x", vec!["none"])` renders as `-x none`.
     pub fn new_with_value(name: &str, value: Vec<&str>) -> Self {
+        CompilerOption::with_kind(name, value, option_table::kind_of_spelling(name))
+    }
+
+    pub fn with_kind(name: &str, value: Vec<&str>, kind: OptionKind) -> Self {
         CompilerOption {
             name: name.to_string(),
             values: value.iter().map(|&v| v.to_string()).collect(),
+            kind,
             is_enabled: true,
             has_value: true,
+            provenance: Provenance::default(),
         }
     }
 
+    /// Parse a single command line argument. Separate options (e.g. `-o`)
+    /// need their argument, so use `option_table::parse_args` for those.
     pub fn from_arg(arg: &str) -> Self {
-        let parts: Vec<&str> = arg.splitn(2, '=').collect();
-        let name = parts[0].to_string();
-        let values = match parts.get(1) {
-            Some(v) => v.split(',').map(|v| v.to_string()).collect(),
-            None => Vec::new(),
-        };
-        let has_value = !values.is_empty();
+        option_table::parse_args(&[arg])
+            .pop()
+            .unwrap_or_else(|| CompilerOption::new(arg))
+    }
 
-        CompilerOption {
-            name,
-            values,
-            is_enabled: true,
-            has_value,
+    pub fn is_input(&self) -> bool {
+        self.kind == OptionKind::Input
+    }
+
+    /// Whether the values are a set that can be extended (e.g. `-fsanitize=`).
+    pub fn is_list(&self) -> bool {
+        option_table::grammar_of_spelling(&self.name) == ValueGrammar::Set
+    }
+
+    /// The command line elements of this option.
+    pub fn to_args(&self) -> Vec<String> {
+        match self.kind {
+            OptionKind::Separate | OptionKind::JoinedOrSeparate if !self.values.is_empty() => {
+                vec![self.name.clone(), self.values.join(",")]
+            }
+            OptionKind::JoinedAndSeparate => match self.values.split_first() {
+                Some((joined, separate)) => {
+                    let mut args = vec![format!("{}{}", self.name, joined)];
+                    args.extend(separate.iter().cloned());
+                    args
+                }
+                None => vec![self.name.clone()],
+            },
+            _ => vec![self.to_string()],
         }
     }
 
@@ -47,24 +94,26 @@ impl CompilerOption {
     }
 
     /// Check if the new_value is already present in the value list. If not, add it to the list.
-    pub fn add_or_update_value(&mut self, new_value: &str) {
+    pub fn add_or_update_value(&mut self, new_value: &str) -> Result<(), ArgusError> {
         if !self.has_value {
-            panic!("Option {} cannot have a value", self.name)
+            return Err(ArgusError::OptionWithoutValue(self.name.clone()));
         }
         if !self.contains(new_value) {
             self.values.push(new_value.to_string());
         }
+        Ok(())
     }
 
     /// Remove the value from the list. After that, if the list is empty, disable the option.
-    pub fn remove_value(&mut self, value_to_remove: &str) {
+    pub fn remove_value(&mut self, value_to_remove: &str) -> Result<(), ArgusError> {
         if !self.has_value {
-            panic!("Option {} cannot have a value", self.name)
+            return Err(ArgusError::OptionWithoutValue(self.name.clone()));
         }
         self.values.retain(|v| v != value_to_remove);
         if self.values.is_empty() {
             self.disable();
         }
+        Ok(())
     }
 
     pub fn disable(&mut self) {
@@ -76,15 +125,62 @@ impl CompilerOption {
     }
 }
 
+/// Where `insert_option` places a new option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertPosition<'a> {
+    /// Before the first input, and before the `-x` that applies to it.
+    BeforeFirstInput,
+    /// After the last input. Inserted inputs are kept out of a `-x` language.
+    AfterLastInput,
+    /// Before the first option with this name, or at the end.
+    Before(&'a str),
+    /// After the last option with this name, or at the end.
+    After(&'a str),
+    /// Before the end of the last linker group, so its members can resolve
+    /// each other. Same as `AfterLastInput` if there is no group.
+    InsideLinkerGroup,
+    /// After the end of the last linker group, or at the end.
+    OutsideLinkerGroups,
+}
+
+fn is_linker_option(option: &CompilerOption) -> bool {
+    option.is_enabled && (option.name == "-Wl," || option.name == "-Xlinker")
+}
+
+fn is_linker_group_end(option: &CompilerOption) -> bool {
+    is_linker_option(option) && (option.contains("--end-group") || option.contains("-)"))
+}
+
+/// Split a linker option ending a group, e.g. `-Wl,--start-group,a.a,--end-group`,
+/// so that the end of the group is an option of its own. Returns its index.
+fn split_before_group_end(options: &mut Vec<CompilerOption>, index: usize) -> usize {
+    let option = &mut options[index];
+    let Some(end) = option
+        .values
+        .iter()
+        .rposition(|value| value == "--end-group" || value == "-)")
+    else {
+        return index;
+    };
+    if end == 0 {
+        return index;
+    }
+    let mut group_end = option.clone();
+    group_end.values = option.values.split_off(end);
+    options.insert(index + 1, group_end);
+    index + 1
+}
+
 pub trait OptionManagement {
     fn get_options(&self, name: &str) -> Vec<&CompilerOption>;
     fn get_mut_options(&mut self, name: &str) -> Vec<&mut CompilerOption>;
+    fn contains_option(&self, option: &CompilerOption) -> bool;
     fn add_or_modify(&mut self, option: &CompilerOption);
-    fn add_or_mix(&mut self, option: &CompilerOption);
+    fn add_or_mix(&mut self, option: &CompilerOption) -> Result<(), ArgusError>;
     fn add_option(&mut self, other: &CompilerOption);
-    fn is_preprocessor(&self) -> bool;
-    fn is_compiling(&self) -> bool;
-    fn is_checking(&self) -> bool;
+    fn insert_option(&mut self, other: &CompilerOption, position: InsertPosition);
+    fn compile_mode(&self) -> CompileMode;
+    fn inputs(&self) -> Vec<InputFile>;
 }
 
 impl OptionManagement for Vec<CompilerOption> {
@@ -100,6 +196,13 @@ impl OptionManagement for Vec<CompilerOption> {
             .collect()
     }
 
+    /// Whether an enabled option with the same name and values exists.
+    fn contains_option(&self, other: &CompilerOption) -> bool {
+        self.get_options(&other.name)
+            .iter()
+            .any(|opt| opt.values == other.values)
+    }
+
     fn add_or_modify(&mut self, other: &CompilerOption) {
         let mut existed_options = self.get_mut_options(&other.name);
         match existed_options.len() {
@@ -108,40 +211,85 @@ impl OptionManagement for Vec<CompilerOption> {
         }
     }
 
-    fn add_or_mix(&mut self, other: &CompilerOption) {
+    /// Merge list values into an existing option. Options that are not lists
+    /// are only added when no identical option is present.
+    fn add_or_mix(&mut self, other: &CompilerOption) -> Result<(), ArgusError> {
+        if !other.is_list() {
+            if !self.contains_option(other) {
+                self.push(other.clone());
+            }
+            return Ok(());
+        }
+
         let mut existed_options = self.get_mut_options(&other.name);
 
         match existed_options.len() {
             1 => {
                 let existed_option = &mut existed_options[0];
-                if existed_option.has_value {
-                    for value in &other.values {
-                        existed_option.add_or_update_value(value);
-                    }
-                } else {
-                    existed_option.values.clone_from(&other.values);
+                for value in &other.values {
+                    existed_option.add_or_update_value(value)?;
                 }
             }
             _ => self.push(other.clone()),
         }
+        Ok(())
     }
 
     fn add_option(&mut self, other: &CompilerOption) {
         self.push(other.clone());
     }
 
-    fn is_preprocessor(&self) -> bool {
-        self.iter().any(|opt| opt.name == "-E" && opt.is_enabled)
+    fn insert_option(&mut self, other: &CompilerOption, position: InsertPosition) {
+        let inputs = self.inputs();
+        let index = match position {
+            InsertPosition::BeforeFirstInput => match inputs.first() {
+                Some(first) => self[..first.index]
+                    .iter()
+                    .rposition(|opt| opt.is_enabled && opt.name == "-x")
+                    .unwrap_or(first.index),
+                None => self.len(),
+            },
+            InsertPosition::AfterLastInput => match inputs.last() {
+                Some(last) if last.language.is_some() && other.is_input() => {
+                    // keep the new input out of the -x language of the last input
+                    let index = last.index + 1;
+                    match self.get(index) {
+                        Some(next) if next.name == "-x" && next.contains("none") => index + 1,
+                        _ => {
+                            self.insert(index, CompilerOption::new_with_value("-x", vec!["none"]));
+                            index + 1
+                        }
+                    }
+                }
+                Some(last) => last.index + 1,
+                None => self.len(),
+            },
+            InsertPosition::Before(name) => self
+                .iter()
+                .position(|opt| opt.is_enabled && opt.name == name)
+                .unwrap_or(self.len()),
+            InsertPosition::After(name) => self
+                .iter()
+                .rposition(|opt| opt.is_enabled && opt.name == name)
+                .map_or(self.len(), |index| index + 1),
+            InsertPosition::InsideLinkerGroup => match self.iter().rposition(is_linker_group_end) {
+                Some(index) => split_before_group_end(self, index),
+                None => return self.insert_option(other, InsertPosition::AfterLastInput),
+            },
+            InsertPosition::OutsideLinkerGroups => self
+                .iter()
+                .rposition(is_linker_group_end)
+                .map_or(self.len(), |index| index + 1),
+        };
+        self.insert(index, other.clone());
     }
 
-    fn is_compiling(&self) -> bool {
-        self.iter().any(|opt| opt.name == "-c" && opt.is_enabled)
+    fn compile_mode(&self) -> CompileMode {
+        CompileMode::from_options(self)
     }
 
-    /// The compiler has no option that is not starting with `-` shall be considered as a checking process.
-    fn is_checking(&self) -> bool {
-        // TODO: support response file mode (e.g. `clang @file`). If you don't know what it is, google it.
-        self.iter().all(|opt| opt.name.starts_with('-'))
+    fn inputs(&self) -> Vec<InputFile> {
+        collect_inputs(self)
     }
 }
 
@@ -150,10 +298,163 @@ impl std::fmt::Display for CompilerOption {
         if !self.is_enabled {
             return Ok(());
         }
-        if self.has_value {
-            write!(f, "{}={}", self.name, self.values.join(","))
-        } else {
-            write!(f, "{}", self.name)
+        match self.kind {
+            OptionKind::Flag | OptionKind::Input => write!(f, "{}", self.name),
+            OptionKind::Separate | OptionKind::JoinedOrSeparate if !self.values.is_empty() => {
+                write!(f, "{} {}", self.name, self.values.join(","))
+            }
+            OptionKind::Separate | OptionKind::JoinedOrSeparate => write!(f, "{}", self.name),
+            OptionKind::JoinedAndSeparate => write!(f, "{}", self.to_args().join(" ")),
+            OptionKind::Joined | OptionKind::CommaJoined => {
+                write!(f, "{}{}", self.name, self.values.join(","))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_table::parse_args;
+
+    fn insert(args: &[&str], other: CompilerOption, position: InsertPosition) -> Vec<String> {
+        let mut options = parse_args(args);
+        options.insert_option(&other, position);
+        options.iter().flat_map(|option| option.to_args()).collect()
+    }
+
+    fn flag() -> CompilerOption {
+        CompilerOption::new("-DX")
+    }
+
+    fn runtime() -> CompilerOption {
+        CompilerOption::new_input("rt.a")
+    }
+
+    #[test]
+    fn before_first_input_skips_the_closest_language() {
+        assert_eq!(
+            insert(
+                &["-O2", "-x", "c", "-x", "c++", "a.cpp"],
+                flag(),
+                InsertPosition::BeforeFirstInput
+            ),
+            ["-O2", "-x", "c", "-DX", "-x", "c++", "a.cpp"]
+        );
+        assert_eq!(
+            insert(
+                &["-O2", "a.c", "b.c"],
+                flag(),
+                InsertPosition::BeforeFirstInput
+            ),
+            ["-O2", "-DX", "a.c", "b.c"]
+        );
+        assert_eq!(
+            insert(&["-O2"], flag(), InsertPosition::BeforeFirstInput),
+            ["-O2", "-DX"]
+        );
+    }
+
+    #[test]
+    fn after_last_input_resets_the_language() {
+        assert_eq!(
+            insert(
+                &["a.o", "b.o", "-o", "a.out"],
+                runtime(),
+                InsertPosition::AfterLastInput
+            ),
+            ["a.o", "b.o", "rt.a", "-o", "a.out"]
+        );
+        assert_eq!(
+            insert(
+                &["-x", "c", "a.c"],
+                runtime(),
+                InsertPosition::AfterLastInput
+            ),
+            ["-x", "c", "a.c", "-x", "none", "rt.a"]
+        );
+    }
+
+    #[test]
+    fn before_and_after_named_options() {
+        assert_eq!(
+            insert(
+                &["a.o", "-o", "a.out"],
+                flag(),
+                InsertPosition::Before("-o")
+            ),
+            ["a.o", "-DX", "-o", "a.out"]
+        );
+        assert_eq!(
+            insert(
+                &["-Lx", "a.o", "-o", "a.out"],
+                flag(),
+                InsertPosition::After("-L")
+            ),
+            ["-Lx", "-DX", "a.o", "-o", "a.out"]
+        );
+        assert_eq!(
+            insert(&["a.o"], flag(), InsertPosition::Before("-o")),
+            ["a.o", "-DX"]
+        );
+    }
+
+    #[test]
+    fn inside_linker_group() {
+        assert_eq!(
+            insert(
+                &[
+                    "a.o",
+                    "-Wl,--start-group",
+                    "liba.a",
+                    "-Wl,--end-group",
+                    "-lm"
+                ],
+                runtime(),
+                InsertPosition::InsideLinkerGroup
+            ),
+            [
+                "a.o",
+                "-Wl,--start-group",
+                "liba.a",
+                "rt.a",
+                "-Wl,--end-group",
+                "-lm"
+            ]
+        );
+        assert_eq!(
+            insert(
+                &["a.o", "-Wl,--start-group,liba.a,libb.a,--end-group", "-lm"],
+                runtime(),
+                InsertPosition::InsideLinkerGroup
+            ),
+            [
+                "a.o",
+                "-Wl,--start-group,liba.a,libb.a",
+                "rt.a",
+                "-Wl,--end-group",
+                "-lm"
+            ]
+        );
+        assert_eq!(
+            insert(
+                &["a.o", "-lm"],
+                runtime(),
+                InsertPosition::InsideLinkerGroup
+            ),
+            ["a.o", "rt.a", "-lm"]
+        );
+    }
+
+    #[test]
+    fn outside_linker_groups() {
+        assert_eq!(
+            insert(
+                &["a.o", "-Wl,--start-group,liba.a,--end-group", "-lm"],
+                runtime(),
+                InsertPosition::OutsideLinkerGroups
+            ),
+            ["a.o", "-Wl,--start-group,liba.a,--end-group", "rt.a", "-lm"]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    compile_mode::CompileMode,
+    env::{ARGUS_CONFIG, ARGUS_ENVS},
+    error::ArgusError,
+    llvm::{split_version_suffix, Toolchain},
+    llvm_compat::LlvmCompat,
+    object::{locate_object, locate_object_variant},
+    target::Arch,
+};
+
+/// A snapshot of the Argus environment variables and the config file.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    vars: HashMap<String, String>,
+    /// Entries of the config file, overridden by `vars`.
+    file_vars: HashMap<String, String>,
+}
+
+impl Config {
+    /// Read all variables listed in `ARGUS_ENVS` from the process environment,
+    /// and the config file given by `ARGUS_CONFIG` or `~/.config/argus/config`.
+    pub fn from_env() -> Result<Self, ArgusError> {
+        let mut config = Config::from_vars(ARGUS_ENVS.iter().filter_map(|(name, _)| {
+            std::env::var(name)
+                .ok()
+                .map(|value| (name.to_string(), value))
+        }));
+
+        match config.get(ARGUS_CONFIG) {
+            // an explicitly given config file has to exist
+            Some(path) => config.file_vars = read_config_file(Path::new(path))?,
+            None => {
+                if let Some(path) = default_config_file_path().filter(|path| path.is_file()) {
+                    config.file_vars = read_config_file(&path)?;
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn from_vars<I: IntoIterator<Item = (String, String)>>(vars: I) -> Self {
+        Config {
+            vars: vars.into_iter().collect(),
+            file_vars: HashMap::new(),
+        }
+    }
+
+    /// The value from the environment, or else from the config file.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_from_env(name).or_else(|| self.get_from_file(name))
+    }
+
+    pub fn get_from_env(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(|value| value.as_str())
+    }
+
+    pub fn get_from_file(&self, name: &str) -> Option<&str> {
+        self.file_vars.get(name).map(|value| value.as_str())
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+}
+
+/// `$XDG_CONFIG_HOME/argus/config`, or `~/.config/argus/config`.
+fn default_config_file_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("argus").join("config"))
+}
+
+/// Read `NAME=value` lines. Empty lines, lines starting with `#` and names
+/// not in `ARGUS_ENVS` are skipped.
+fn read_config_file(path: &Path) -> Result<HashMap<String, String>, ArgusError> {
+    let content = fs::read_to_string(path).map_err(|source| ArgusError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .filter(|(name, _)| ARGUS_ENVS.iter().any(|(known, _)| known == name))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect())
+}
+
+/// Everything a visitor may depend on besides the options themselves.
+#[derive(Debug, Clone)]
+pub struct VisitorContext {
+    pub config: Config,
+    /// argv[0], e.g. `argus` or `argus++`.
+    pub program_name: String,
+    pub toolchain: Toolchain,
+    pub object_search_paths: Vec<PathBuf>,
+    /// The mode of the original command.
+    pub mode: CompileMode,
+}
+
+impl VisitorContext {
+    /// Whether Argus was invoked as the C++ compiler, e.g. `argus++` or `argus-clang++-16`.
+    pub fn is_cxx(&self) -> bool {
+        split_version_suffix(&self.program_name).0.ends_with("++")
+    }
+
+    /// What the LLVM release of the toolchain supports.
+    pub fn llvm_compat(&self) -> LlvmCompat {
+        LlvmCompat::new(self.toolchain.major_version)
+    }
+
+    /// Find an object in the object search paths, see `object::locate_object`.
+    pub fn locate_object(&self, what: &'static str, obj: &str) -> Result<String, ArgusError> {
+        locate_object(what, obj, &self.object_search_paths)
+    }
+
+    /// Find the variant of an object for an architecture, see
+    /// `object::locate_object_variant`.
+    pub fn locate_object_for(
+        &self,
+        what: &'static str,
+        obj: &str,
+        arch: &Arch,
+    ) -> Result<String, ArgusError> {
+        locate_object_variant(what, obj, arch, &self.object_search_paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{ARGUS_DEBUG, ARGUS_OBJECT_PATH};
+
+    #[test]
+    fn the_environment_overrides_the_config_file() {
+        let path = std::env::temp_dir().join(format!("argus-config-{}", std::process::id()));
+        fs::write(
+            &path,
+            "# objects\nARGUS_OBJECT_PATH = /opt/argus/lib\n\nARGUS_DEBUG=1\nNOT_ARGUS=1\nno value\n",
+        )
+        .unwrap();
+        let file_vars = read_config_file(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(file_vars.len(), 2);
+
+        let config = Config {
+            vars: HashMap::from([(ARGUS_OBJECT_PATH.to_string(), String::from("/objects"))]),
+            file_vars,
+        };
+        assert_eq!(config.get(ARGUS_OBJECT_PATH), Some("/objects"));
+        assert_eq!(
+            config.get_from_file(ARGUS_OBJECT_PATH),
+            Some("/opt/argus/lib")
+        );
+        assert!(config.is_set(ARGUS_DEBUG));
+        assert!(!config.is_set("NOT_ARGUS"));
+    }
+
+    #[test]
+    fn a_missing_config_file_is_an_error() {
+        assert!(matches!(
+            read_config_file(Path::new("/nonexistent/argus/config")),
+            Err(ArgusError::Io { .. })
+        ));
+    }
+}
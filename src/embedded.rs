@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::llvm::cache_dir;
+
+// `EMBEDDED_OBJECTS`: (file name, content) of the objects embedded at build
+// time, see build.rs.
+#[cfg(feature = "embed-objects")]
+include!(concat!(env!("OUT_DIR"), "/embedded_objects.rs"));
+
+#[cfg(not(feature = "embed-objects"))]
+pub static EMBEDDED_OBJECTS: &[(&str, &[u8])] = &[];
+
+/// Extract the embedded object with this file name, unless it was extracted
+/// before. Objects are stored by content in `<cache dir>/objects/<hash>/<name>`,
+/// so different Argus builds never share a file.
+pub fn extract(name: &str) -> Option<PathBuf> {
+    let &(name, content) = EMBEDDED_OBJECTS
+        .iter()
+        .find(|(embedded, _)| *embedded == name)?;
+    let dir = cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("argus"))
+        .join("objects")
+        .join(format!("{:016x}", fnv1a(content)));
+    let path = dir.join(name);
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() == content.len() as u64) {
+        return Some(path);
+    }
+    write(&dir, name, content).ok()?;
+    Some(path)
+}
+
+pub(crate) fn write(dir: &std::path::Path, name: &str, content: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    // concurrent compiles may extract the same object, so write a private
+    // file and rename it into place
+    let temporary = dir.join(format!(".{}.{}", name, std::process::id()));
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, dir.join(name)).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`.
+pub(crate) fn fnv1a(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_the_reference() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn write_leaves_no_temporary_file() {
+        let dir = std::env::temp_dir().join(format!("argus-embedded-{}", std::process::id()));
+        write(&dir, "rt.o", b"old").unwrap();
+        write(&dir, "rt.o", b"new").unwrap();
+        assert_eq!(fs::read(dir.join("rt.o")).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_embedded_objects_are_extracted() {
+        assert_eq!(extract("not-embedded.o"), None);
+    }
+}
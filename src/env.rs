@@ -18,6 +18,16 @@ macro_rules! define_env_vars {
 // Use the macro to define environment variables and their descriptions
 define_env_vars! {
     ARGUS_DEBUG: "ARGUS_DEBUG" => "Enable debugging for Argus.",
+    ARGUS_EXPLAIN: "ARGUS_EXPLAIN" => "Print the changes of each visitor and where every option comes from.",
+    ARGUS_ON_ERROR: "ARGUS_ON_ERROR" => "What to do if a visitor fails: fail (default), warn or fallback.",
+    ARGUS_VISITORS: "ARGUS_VISITORS" => "Comma separated list of visitors to run instead of the default ones.",
+    ARGUS_DISABLE_VISITORS: "ARGUS_DISABLE_VISITORS" => "Comma separated list of visitors not to run.",
+    ARGUS_CONFIG: "ARGUS_CONFIG" => "Config file with NAME=value lines, instead of ~/.config/argus/config.",
+    ARGUS_OBJECT_PATH: "ARGUS_OBJECT_PATH" => "Colon separated directories to search for objects and plugins.",
+    ARGUS_NO_CACHE: "ARGUS_NO_CACHE" => "Run llvm-config instead of reading the cached toolchain.",
+    LLVM_CONFIG: "LLVM_CONFIG" => "Path or name of the llvm-config to use.",
+    ARGUS_CC: "ARGUS_CC" => "C compiler to run instead of the clang of the LLVM toolchain.",
+    ARGUS_CXX: "ARGUS_CXX" => "C++ compiler to run instead of the clang++ of the LLVM toolchain.",
 
     // options to enable variables
     ADD_DRIVER: "ADD_DRIVER" => "Enable harness support, replace libFuzzer options with driver.",
@@ -31,9 +41,28 @@ define_env_vars! {
     DRIVER: "BANDFUZZ_DRIVER" => "Library used as the fuzzer driver (libFuzzer mode).",
     ENABLE_ASAN: "AFL_USE_ASAN" => "Enable AddressSanitizer.",
     ENABLE_COVSAN: "BANDFUZZ_USECOV" => "Enable coverage sanitizer.",
+    COVERAGE: "BANDFUZZ_COVERAGE" => "Comma separated SanitizerCoverage features, e.g. inline-8bit-counters,pc-table.",
     ENABLE_MSAN: "AFL_USE_MSAN" => "Enable MemorySanitizer.",
     ENABLE_UBSAN: "AFL_USE_UBSAN" => "Enable UndefinedBehaviorSanitizer.",
+    UBSAN_CHECKS: "ARGUS_UBSAN_CHECKS" => "Comma separated UBSan checks and groups enabled by AFL_USE_UBSAN, e.g. integer,bounds (default: undefined).",
+    UBSAN_MODE: "ARGUS_UBSAN_MODE" => "How UBSan reports errors: trap (default), minimal, minimal-recover, full or full-recover.",
+    MSAN_LIBCXX: "ARGUS_MSAN_LIBCXX" => "Install prefix of an MSan-instrumented libc++, used by C++ MSan builds.",
+    MSAN_ORIGINS: "ARGUS_MSAN_ORIGINS" => "MSan origin tracking depth: 0, 1 or 2 (default).",
+    ENABLE_TSAN: "AFL_USE_TSAN" => "Enable ThreadSanitizer.",
+    ENABLE_LSAN: "AFL_USE_LSAN" => "Enable LeakSanitizer.",
+    ENABLE_HWASAN: "AFL_USE_HWASAN" => "Enable HWAddressSanitizer.",
+    ENABLE_CFISAN: "AFL_USE_CFISAN" => "Enable Control Flow Integrity (adds -flto -fvisibility=hidden).",
+    ENABLE_SAFESTACK: "AFL_USE_SAFESTACK" => "Enable SafeStack.",
+    ENABLE_KCFI: "AFL_USE_KCFI" => "Enable kernel Control Flow Integrity.",
     NOSANITIZER: "BANDFUZZ_NOSAN" => "Disable all sanitizers.",
+    ARGUS_SANITIZER_CONFLICT: "ARGUS_SANITIZER_CONFLICT" => "What to do with incompatible sanitizers: fail (default) or precedence.",
+    ASAN_DEFAULT_OPTIONS: "ARGUS_ASAN_DEFAULT_OPTIONS" => "ASAN_OPTIONS baked into sanitized executables, e.g. abort_on_error=1:symbolize=0.",
+    UBSAN_DEFAULT_OPTIONS: "ARGUS_UBSAN_DEFAULT_OPTIONS" => "UBSAN_OPTIONS baked into sanitized executables.",
+    MSAN_DEFAULT_OPTIONS: "ARGUS_MSAN_DEFAULT_OPTIONS" => "MSAN_OPTIONS baked into sanitized executables.",
+    LSAN_DEFAULT_OPTIONS: "ARGUS_LSAN_DEFAULT_OPTIONS" => "LSAN_OPTIONS baked into sanitized executables, e.g. detect_leaks=0.",
+    COVERAGE_ALLOWLIST: "ARGUS_COVERAGE_ALLOWLIST" => "Comma separated source globs and fun:<name> entries to restrict coverage instrumentation to.",
+    COVERAGE_DENYLIST: "ARGUS_COVERAGE_DENYLIST" => "Comma separated source globs and fun:<name> entries excluded from coverage instrumentation.",
+    SANITIZER_IGNORELIST: "ARGUS_SANITIZER_IGNORELIST" => "Comma separated source globs and fun:<name> entries excluded from sanitizer instrumentation.",
     OPT_LEVEL: "BANDFUZZ_OPT" => "Optimization level for the target.",
 }
 
@@ -0,0 +1,227 @@
+use std::{fmt, io, path::PathBuf};
+
+use colored::*;
+
+use crate::{
+    context::Config,
+    env::ARGUS_ON_ERROR,
+    env::ARGUS_SANITIZER_CONFLICT,
+    object::ObjectProblem,
+    sanitizer::{CoverageProblem, SanitizerConflict, UbsanProblem},
+    target::Arch,
+};
+
+#[derive(Debug)]
+pub enum ArgusError {
+    /// An object (runtime, driver, pass plugin, ...) could not be found.
+    ObjectNotFound {
+        what: &'static str,
+        name: String,
+        searched: Vec<PathBuf>,
+    },
+    /// None of the variants of an object for the target architecture exists.
+    ObjectVariantNotFound {
+        what: &'static str,
+        arch: Arch,
+        names: Vec<String>,
+        searched: Vec<PathBuf>,
+    },
+    /// An object cannot be linked into the command.
+    InvalidObject {
+        path: PathBuf,
+        problem: ObjectProblem,
+    },
+    /// `llvm-config` could not be run or its output could not be parsed.
+    LlvmConfig(io::Error),
+    /// clang or clang++ is missing from the LLVM toolchain.
+    CompilerNotFound(PathBuf),
+    /// The LLVM release is newer than the compatibility tables.
+    UnknownLlvmVersion {
+        version: u32,
+        assumed: u32,
+    },
+    /// A value was added to or removed from an option that takes none.
+    OptionWithoutValue(String),
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The enabled sanitizers violate the compatibility matrix.
+    SanitizerConflict(SanitizerConflict),
+    /// The coverage spec or the combined coverage features are invalid.
+    InvalidCoverage(CoverageProblem),
+    /// The UBSan check list or mode is invalid.
+    InvalidUbsan(UbsanProblem),
+    /// A helper object Argus generates failed to compile.
+    CompileFailed {
+        what: &'static str,
+        command: String,
+        stderr: String,
+    },
+    /// An environment variable has a value Argus does not understand.
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    UnknownVisitor(String),
+    DuplicateVisitor(&'static str),
+    /// The before/after constraints of these visitors form a cycle.
+    VisitorCycle(Vec<&'static str>),
+}
+
+impl fmt::Display for ArgusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgusError::ObjectNotFound {
+                what,
+                name,
+                searched,
+            } => {
+                let searched: Vec<String> = searched
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "could not find {} `{}`, searched: {}",
+                    what,
+                    name,
+                    searched.join(", ")
+                )
+            }
+            ArgusError::ObjectVariantNotFound {
+                what,
+                arch,
+                names,
+                searched,
+            } => {
+                let searched: Vec<String> = searched
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "could not find a {} for {} (tried {}), searched: {}",
+                    what,
+                    arch,
+                    names.join(", "),
+                    searched.join(", ")
+                )
+            }
+            ArgusError::InvalidObject { path, problem } => {
+                write!(f, "{}: {}", path.display(), problem)
+            }
+            ArgusError::LlvmConfig(error) => write!(f, "llvm-config failed: {}", error),
+            ArgusError::CompilerNotFound(path) => {
+                write!(f, "compiler not found at {}", path.display())
+            }
+            ArgusError::UnknownLlvmVersion { version, assumed } => write!(
+                f,
+                "unknown LLVM version {}, assuming the behavior of LLVM {}",
+                version, assumed
+            ),
+            ArgusError::OptionWithoutValue(name) => {
+                write!(f, "option `{}` cannot have a value", name)
+            }
+            ArgusError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ArgusError::SanitizerConflict(conflict) => write!(
+                f,
+                "{} (set {}=precedence to resolve it automatically)",
+                conflict, ARGUS_SANITIZER_CONFLICT
+            ),
+            ArgusError::InvalidCoverage(problem) => write!(f, "{}", problem),
+            ArgusError::InvalidUbsan(problem) => write!(f, "{}", problem),
+            ArgusError::CompileFailed {
+                what,
+                command,
+                stderr,
+            } => write!(f, "compiling the {} failed: {}\n{}", what, command, stderr),
+            ArgusError::InvalidValue {
+                name,
+                value,
+                expected,
+            } => write!(f, "invalid {}=`{}`, expected {}", name, value, expected),
+            ArgusError::UnknownVisitor(name) => write!(f, "unknown visitor `{}`", name),
+            ArgusError::DuplicateVisitor(name) => {
+                write!(f, "visitor `{}` is registered twice", name)
+            }
+            ArgusError::VisitorCycle(names) => write!(
+                f,
+                "visitor ordering constraints form a cycle between {}",
+                names.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArgusError {}
+
+/// What to do when a visitor fails, selected by `ARGUS_ON_ERROR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// `fail` (default): stop without running the compiler.
+    Fail,
+    /// `warn`: report the error, undo the visitor's changes and go on.
+    WarnAndSkip,
+    /// `fallback`: report the error and run the original, unmodified command.
+    PlainCompile,
+}
+
+impl ErrorPolicy {
+    pub fn from_config(config: &Config) -> Result<Self, ArgusError> {
+        match config.get(ARGUS_ON_ERROR) {
+            None | Some("fail") => Ok(ErrorPolicy::Fail),
+            Some("warn") => Ok(ErrorPolicy::WarnAndSkip),
+            Some("fallback") => Ok(ErrorPolicy::PlainCompile),
+            Some(value) => Err(ArgusError::InvalidValue {
+                name: ARGUS_ON_ERROR,
+                value: value.to_string(),
+                expected: "fail, warn or fallback",
+            }),
+        }
+    }
+}
+
+/// Print a single line diagnostic, prefixed with where it comes from.
+pub fn report(level: &str, source: &str, message: &dyn fmt::Display) {
+    let level = if level == "error" {
+        level.red().bold()
+    } else {
+        level.yellow().bold()
+    };
+    eprintln!(
+        "[{}::{}] {}: {}",
+        "ARGUS".italic().bold(),
+        level,
+        source,
+        message
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(value: &str) -> Result<ErrorPolicy, ArgusError> {
+        ErrorPolicy::from_config(&Config::from_vars([(
+            ARGUS_ON_ERROR.to_string(),
+            value.to_string(),
+        )]))
+    }
+
+    #[test]
+    fn error_policy_values() {
+        assert_eq!(
+            ErrorPolicy::from_config(&Config::default()).unwrap(),
+            ErrorPolicy::Fail
+        );
+        assert_eq!(policy("fail").unwrap(), ErrorPolicy::Fail);
+        assert_eq!(policy("warn").unwrap(), ErrorPolicy::WarnAndSkip);
+        assert_eq!(policy("fallback").unwrap(), ErrorPolicy::PlainCompile);
+        assert!(matches!(
+            policy("fallbak"),
+            Err(ArgusError::InvalidValue { value, .. }) if value == "fallbak"
+        ));
+    }
+}
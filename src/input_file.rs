@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use crate::compiler_option::CompilerOption;
+
+/// The type of an input, either from its extension or from the `-x` language in effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    C,
+    Cxx,
+    ObjC,
+    ObjCxx,
+    /// `.s`, assembler that is not preprocessed.
+    Assembler,
+    /// `.S`, assembler that goes through the preprocessor first.
+    PreprocessedAssembler,
+    /// `.bc` or `.ll`.
+    LlvmBitcode,
+    Object,
+    StaticArchive,
+    SharedLibrary,
+    LinkerScript,
+}
+
+impl InputKind {
+    /// Map a `-x` language to an input kind. `none` and unknown languages return `None`.
+    pub fn from_language(language: &str) -> Option<Self> {
+        match language {
+            "c" | "c-header" | "cpp-output" => Some(InputKind::C),
+            "c++" | "c++-header" | "c++-cpp-output" => Some(InputKind::Cxx),
+            "objective-c" | "objective-c-header" | "objective-c-cpp-output" => {
+                Some(InputKind::ObjC)
+            }
+            "objective-c++" | "objective-c++-header" | "objective-c++-cpp-output" => {
+                Some(InputKind::ObjCxx)
+            }
+            "assembler" => Some(InputKind::Assembler),
+            "assembler-with-cpp" => Some(InputKind::PreprocessedAssembler),
+            "ir" => Some(InputKind::LlvmBitcode),
+            _ => None,
+        }
+    }
+
+    /// Guess the input kind from the file name. Like clang, anything unknown is
+    /// passed to the linker as an object.
+    pub fn from_path(path: &str) -> Self {
+        if path == "-" {
+            return InputKind::C;
+        }
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // versioned shared libraries, e.g. libfoo.so.1.2
+        if file_name.contains(".so.") {
+            return InputKind::SharedLibrary;
+        }
+        let extension = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "c" | "h" | "i" => InputKind::C,
+            "cc" | "cp" | "cpp" | "cxx" | "c++" | "C" | "CC" | "CPP" | "hh" | "hpp" | "hxx"
+            | "ii" => InputKind::Cxx,
+            "m" | "mi" => InputKind::ObjC,
+            "mm" | "M" | "mii" => InputKind::ObjCxx,
+            "s" => InputKind::Assembler,
+            "S" | "sx" => InputKind::PreprocessedAssembler,
+            "bc" | "ll" => InputKind::LlvmBitcode,
+            "a" | "lib" => InputKind::StaticArchive,
+            "so" | "dylib" => InputKind::SharedLibrary,
+            "ld" | "lds" => InputKind::LinkerScript,
+            _ => InputKind::Object,
+        }
+    }
+
+    /// Whether the input is compiled (or assembled) before linking.
+    pub fn is_source(&self) -> bool {
+        matches!(
+            self,
+            InputKind::C
+                | InputKind::Cxx
+                | InputKind::ObjC
+                | InputKind::ObjCxx
+                | InputKind::Assembler
+                | InputKind::PreprocessedAssembler
+                | InputKind::LlvmBitcode
+        )
+    }
+
+    pub fn is_cxx(&self) -> bool {
+        matches!(self, InputKind::Cxx | InputKind::ObjCxx)
+    }
+}
+
+/// An input of the command together with the position of its option.
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    /// Index of the input in the option list.
+    pub index: usize,
+    pub path: String,
+    pub kind: InputKind,
+    /// The `-x` language in effect for this input, if any.
+    pub language: Option<String>,
+}
+
+/// Collect the enabled inputs, tracking the `-x` language as it changes.
+pub fn collect_inputs(options: &[CompilerOption]) -> Vec<InputFile> {
+    let mut language: Option<String> = None;
+    let mut inputs = Vec::new();
+
+    for (index, option) in options.iter().enumerate() {
+        if !option.is_enabled {
+            continue;
+        }
+        if option.name == "-x" {
+            language = option.values.first().filter(|&l| l != "none").cloned();
+        } else if option.is_input() {
+            let kind = language
+                .as_deref()
+                .and_then(InputKind::from_language)
+                .unwrap_or_else(|| InputKind::from_path(&option.name));
+            inputs.push(InputFile {
+                index,
+                path: option.name.clone(),
+                kind,
+                language: language.clone(),
+            });
+        }
+    }
+
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_table::parse_args;
+
+    fn inputs(args: &[&str]) -> Vec<(usize, String, InputKind, Option<String>)> {
+        collect_inputs(&parse_args(args))
+            .into_iter()
+            .map(|input| (input.index, input.path, input.kind, input.language))
+            .collect()
+    }
+
+    #[test]
+    fn kinds_follow_the_extension() {
+        assert_eq!(
+            inputs(&["-c", "a.c", "b.cpp", "c.S", "libd.so.1", "e.a", "f.o"])
+                .into_iter()
+                .map(|(_, _, kind, _)| kind)
+                .collect::<Vec<_>>(),
+            [
+                InputKind::C,
+                InputKind::Cxx,
+                InputKind::PreprocessedAssembler,
+                InputKind::SharedLibrary,
+                InputKind::StaticArchive,
+                InputKind::Object,
+            ]
+        );
+    }
+
+    #[test]
+    fn language_switches_with_x() {
+        assert_eq!(
+            inputs(&[
+                "a.c",
+                "-x",
+                "c++",
+                "b.h",
+                "-xassembler",
+                "c.txt",
+                "-x",
+                "none",
+                "d.o"
+            ]),
+            [
+                (0, String::from("a.c"), InputKind::C, None),
+                (
+                    2,
+                    String::from("b.h"),
+                    InputKind::Cxx,
+                    Some(String::from("c++"))
+                ),
+                (
+                    4,
+                    String::from("c.txt"),
+                    InputKind::Assembler,
+                    Some(String::from("assembler"))
+                ),
+                (6, String::from("d.o"), InputKind::Object, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn stdin_is_an_input() {
+        assert_eq!(
+            inputs(&["-E", "-"]),
+            [(1, String::from("-"), InputKind::C, None)]
+        );
+        assert_eq!(
+            inputs(&["-x", "c++", "-c", "-"]),
+            [(
+                2,
+                String::from("-"),
+                InputKind::Cxx,
+                Some(String::from("c++"))
+            )]
+        );
+    }
+}
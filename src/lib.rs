@@ -1,6 +1,17 @@
+pub mod compile_mode;
 pub mod compiler_option;
+pub mod context;
+pub mod embedded;
 pub mod env;
+pub mod error;
+pub mod input_file;
 pub mod llvm;
+pub mod llvm_compat;
 pub mod object;
 pub mod option_manager;
+pub mod option_table;
 pub mod option_visitors;
+pub mod provenance;
+pub mod sanitizer;
+pub mod special_case_list;
+pub mod target;
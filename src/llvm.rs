@@ -1,70 +1,356 @@
+use std::fmt;
+use std::fs;
 use std::io::{self, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
-/// Get LLVM bindir by running `llvm-config --bindir`
-pub fn get_llvm_bindir() -> io::Result<PathBuf> {
-    let output = std::process::Command::new("llvm-config")
-        .arg("--bindir")
-        .output()?;
+use crate::{
+    context::Config,
+    embedded::fnv1a,
+    env::{ARGUS_CC, ARGUS_CXX, ARGUS_NO_CACHE, LLVM_CONFIG},
+    error::ArgusError,
+};
 
-    let path_str =
-        String::from_utf8(output.stdout).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-    Ok(PathBuf::from(path_str.trim()))
+/// The LLVM toolchain used for this invocation.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    /// The `llvm-config` the toolchain was detected with.
+    pub llvm_config: PathBuf,
+    pub bindir: PathBuf,
+    pub libdir: PathBuf,
+    pub major_version: u32,
+    /// Set by `ARGUS_CC`, replaces the clang of the toolchain.
+    pub cc: Option<PathBuf>,
+    /// Set by `ARGUS_CXX`, replaces the clang++ of the toolchain.
+    pub cxx: Option<PathBuf>,
 }
 
-/// Get LLVM libdir by running `llvm-config --libdir`
-pub fn get_llvm_libdir() -> io::Result<PathBuf> {
-    let output = std::process::Command::new("llvm-config")
-        .arg("--libdir")
-        .output()?;
+impl Toolchain {
+    /// Detect the toolchain with `llvm-config`, or read it from the cache if
+    /// `llvm-config` did not change since it was cached. See `find_llvm_config`
+    /// for which `llvm-config` is used.
+    pub fn detect(config: &Config, program_name: &str) -> Result<Self, ArgusError> {
+        let llvm_config = find_llvm_config(config, program_name)?;
+        // key the cache on the real file, a symlink may be retargeted to another LLVM
+        let llvm_config = fs::canonicalize(&llvm_config).unwrap_or(llvm_config);
 
-    let path_str =
-        String::from_utf8(output.stdout).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-    Ok(PathBuf::from(path_str.trim()))
+        let mut toolchain = Toolchain::detect_cached(config, llvm_config)?;
+        toolchain.cc = config.get(ARGUS_CC).map(resolve_program);
+        toolchain.cxx = config.get(ARGUS_CXX).map(resolve_program);
+        Ok(toolchain)
+    }
+
+    fn detect_cached(config: &Config, llvm_config: PathBuf) -> Result<Self, ArgusError> {
+        if config.is_set(ARGUS_NO_CACHE) {
+            return Toolchain::query(llvm_config);
+        }
+
+        let cache = cache_path(&llvm_config);
+        let mtime = modified_time(&llvm_config);
+        if let (Some(cache), Some(mtime)) = (&cache, &mtime) {
+            if let Some(toolchain) = Toolchain::load(cache, &llvm_config, mtime) {
+                return Ok(toolchain);
+            }
+        }
+
+        let toolchain = Toolchain::query(llvm_config)?;
+        if let (Some(cache), Some(mtime)) = (&cache, &mtime) {
+            // the cache is best effort, a failure only costs the next compile a query
+            let _ = toolchain.store(cache, mtime);
+        }
+        Ok(toolchain)
+    }
+
+    /// Run `llvm-config` once for everything we need.
+    fn query(llvm_config: PathBuf) -> Result<Self, ArgusError> {
+        let output = std::process::Command::new(&llvm_config)
+            .args(["--bindir", "--libdir", "--version"])
+            .output()
+            .map_err(ArgusError::LlvmConfig)?;
+        let output = String::from_utf8(output.stdout)
+            .map_err(|e| ArgusError::LlvmConfig(io::Error::new(ErrorKind::InvalidData, e)))?;
+
+        let mut lines = output.lines().map(|line| line.trim());
+        let (Some(bindir), Some(libdir), Some(version)) =
+            (lines.next(), lines.next(), lines.next())
+        else {
+            return Err(ArgusError::LlvmConfig(io::Error::new(
+                ErrorKind::InvalidData,
+                "unexpected llvm-config output",
+            )));
+        };
+        Ok(Toolchain {
+            bindir: PathBuf::from(bindir),
+            libdir: PathBuf::from(libdir),
+            major_version: parse_major_version(version).map_err(ArgusError::LlvmConfig)?,
+            llvm_config,
+            cc: None,
+            cxx: None,
+        })
+    }
+
+    /// Read the cached toolchain, if it was cached for this `llvm-config`.
+    ///
+    /// The cache has one value per line: the `llvm-config` path, its mtime,
+    /// bindir, libdir and major version.
+    fn load(cache: &Path, llvm_config: &Path, mtime: &str) -> Option<Self> {
+        let content = fs::read_to_string(cache).ok()?;
+        let mut lines = content.lines();
+        if Path::new(lines.next()?) != llvm_config || lines.next()? != mtime {
+            return None;
+        }
+        Some(Toolchain {
+            llvm_config: llvm_config.to_path_buf(),
+            bindir: PathBuf::from(lines.next()?),
+            libdir: PathBuf::from(lines.next()?),
+            major_version: lines.next()?.parse().ok()?,
+            cc: None,
+            cxx: None,
+        })
+    }
+
+    fn store(&self, cache: &Path, mtime: &str) -> io::Result<()> {
+        if let Some(dir) = cache.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = format!(
+            "{}\n{}\n{}\n{}\n{}\n",
+            self.llvm_config.display(),
+            mtime,
+            self.bindir.display(),
+            self.libdir.display(),
+            self.major_version
+        );
+        // concurrent compiles may write the cache at the same time, so write a
+        // private file and rename it over the cache
+        let temporary = cache.with_extension(std::process::id().to_string());
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, cache).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+
+    /// Get the path to the clang binary
+    pub fn clang(&self) -> Result<PathBuf, ArgusError> {
+        self.compiler(self.cc.as_ref(), "clang")
+    }
+
+    /// Get the path to the clang++ binary
+    pub fn clang_plus_plus(&self) -> Result<PathBuf, ArgusError> {
+        self.compiler(self.cxx.as_ref(), "clang++")
+    }
+
+    fn compiler(&self, explicit: Option<&PathBuf>, name: &str) -> Result<PathBuf, ArgusError> {
+        if let Some(path) = explicit {
+            return if path.is_file() {
+                Ok(path.clone())
+            } else {
+                Err(ArgusError::CompilerNotFound(path.clone()))
+            };
+        }
+        // some distributions only ship the versioned name, e.g. clang-16
+        let path = self.bindir.join(name);
+        let versioned = self.bindir.join(format!("{}-{}", name, self.major_version));
+        // Check if the compiler exists
+        if path.exists() {
+            Ok(path)
+        } else if versioned.exists() {
+            Ok(versioned)
+        } else {
+            Err(ArgusError::CompilerNotFound(path))
+        }
+    }
+}
+
+impl fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "LLVM {} from {}",
+            self.major_version,
+            self.llvm_config.display()
+        )?;
+        if let Some(cc) = &self.cc {
+            write!(f, ", {}={}", ARGUS_CC, cc.display())?;
+        }
+        if let Some(cxx) = &self.cxx {
+            write!(f, ", {}={}", ARGUS_CXX, cxx.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The compiler that runs the original command when the toolchain cannot be
+/// detected: `ARGUS_CC`, or `ARGUS_CXX` if Argus is the C++ compiler, and
+/// else `clang` or `clang++` from `PATH`.
+pub fn fallback_compiler(config: &Config, program_name: &str) -> PathBuf {
+    if split_version_suffix(program_name).0.ends_with("++") {
+        resolve_program(config.get(ARGUS_CXX).unwrap_or("clang++"))
+    } else {
+        resolve_program(config.get(ARGUS_CC).unwrap_or("clang"))
+    }
+}
+
+/// Split a version suffix off a program name, e.g. `argus-clang++-16` gives
+/// `("argus-clang++", Some(16))`. Only the file name is considered.
+pub fn split_version_suffix(program_name: &str) -> (&str, Option<u32>) {
+    let name = program_name.rsplit('/').next().unwrap_or(program_name);
+    match name.rsplit_once('-') {
+        Some((base, version))
+            if !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (base, version.parse().ok())
+        }
+        _ => (name, None),
+    }
 }
 
-/// Get LLVM major version by running `llvm-config --version`
-/// and parsing the major version from the output
-pub fn get_llvm_major_version() -> io::Result<u32> {
-    let output = std::process::Command::new("llvm-config")
-        .arg("--version")
-        .output()?;
-
-    let version_str =
-        String::from_utf8(output.stdout).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-    let version_parts: Vec<&str> = version_str.trim().split('.').collect();
-    let major_version = version_parts
-        .first()
+/// Pick the `llvm-config` to use, in this order:
+/// 1. `LLVM_CONFIG`, a path or a name in `PATH`;
+/// 2. `llvm-config-NN` if Argus is invoked with a version suffix, e.g. `argus-clang-16`;
+/// 3. `llvm-config` in `PATH`;
+/// 4. the newest `llvm-config-NN` in `PATH`.
+fn find_llvm_config(config: &Config, program_name: &str) -> Result<PathBuf, ArgusError> {
+    let not_found =
+        |message: String| ArgusError::LlvmConfig(io::Error::new(ErrorKind::NotFound, message));
+
+    if let Some(llvm_config) = config.get(LLVM_CONFIG) {
+        let path = resolve_program(llvm_config);
+        if !path.is_file() {
+            return Err(not_found(format!(
+                "{}={} does not exist",
+                LLVM_CONFIG, llvm_config
+            )));
+        }
+        return Ok(path);
+    }
+
+    if let (_, Some(version)) = split_version_suffix(program_name) {
+        let name = format!("llvm-config-{}", version);
+        return find_in_path(&name).ok_or_else(|| not_found(format!("{} not found in PATH", name)));
+    }
+
+    find_in_path("llvm-config")
+        .or_else(newest_versioned_llvm_config)
+        .ok_or_else(|| not_found(String::from("llvm-config not found in PATH")))
+}
+
+/// The `llvm-config-NN` in `PATH` with the highest `NN`.
+fn newest_versioned_llvm_config() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            match split_version_suffix(&name) {
+                ("llvm-config", Some(version)) => Some((version, entry.path())),
+                _ => None,
+            }
+        })
+        .filter(|(_, path)| path.is_file())
+        // the first directory in PATH wins for the same version
+        .fold(
+            None,
+            |newest: Option<(u32, PathBuf)>, (version, path)| match newest {
+                Some((newest_version, _)) if newest_version >= version => newest,
+                _ => Some((version, path)),
+            },
+        )
+        .map(|(_, path)| path)
+}
+
+/// A program given by path, or by name to be looked up in `PATH`.
+fn resolve_program(program: &str) -> PathBuf {
+    if program.contains('/') {
+        PathBuf::from(program)
+    } else {
+        find_in_path(program).unwrap_or_else(|| PathBuf::from(program))
+    }
+}
+
+/// Parse the major version from e.g. `16.0.6` or `17.0.0git`.
+fn parse_major_version(version: &str) -> io::Result<u32> {
+    let major_version = version
+        .split('.')
+        .next()
         .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Could not parse LLVM version"))?;
     major_version
         .parse()
         .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
 }
 
-/// Get the path to the clang binary
-pub fn get_clang_path() -> io::Result<PathBuf> {
-    let bindir = get_llvm_bindir()?;
-    let clang_path = bindir.join("clang");
-    // Check if clang exists
-    if !clang_path.exists() {
-        return Err(io::Error::new(
-            ErrorKind::NotFound,
-            format!("clang not found at {:?}", clang_path),
-        ));
-    }
-    Ok(clang_path)
+/// Find an executable in `PATH`, like the shell does.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// `<cache dir>/toolchain-<hash>`, with one file per `llvm-config`.
+fn cache_path(llvm_config: &Path) -> Option<PathBuf> {
+    let hash = fnv1a(llvm_config.as_os_str().as_encoded_bytes());
+    Some(cache_dir()?.join(format!("toolchain-{:016x}", hash)))
+}
+
+/// `$XDG_CACHE_HOME/argus`, or `~/.cache/argus`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("argus"))
+}
+
+fn modified_time(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!(
+        "{}.{:09}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    ))
 }
 
-/// Get the path to the clang++ binary
-pub fn get_clang_plus_plus_path() -> io::Result<PathBuf> {
-    let bindir = get_llvm_bindir()?;
-    let clang_plus_plus_path = bindir.join("clang++");
-    // Check if clang++ exists
-    if !clang_plus_plus_path.exists() {
-        return Err(io::Error::new(
-            ErrorKind::NotFound,
-            format!("clang++ not found at {:?}", clang_plus_plus_path),
-        ));
-    }
-    Ok(clang_plus_plus_path)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn major_versions() {
+        assert_eq!(parse_major_version("16.0.6").unwrap(), 16);
+        assert_eq!(parse_major_version("17").unwrap(), 17);
+        assert!(parse_major_version("17git").is_err());
+    }
+
+    #[test]
+    fn the_cache_is_keyed_on_llvm_config_and_its_mtime() {
+        let dir = std::env::temp_dir().join(format!("argus-llvm-cache-{}", std::process::id()));
+        let cache = dir.join("toolchain");
+        let llvm_config = PathBuf::from("/usr/lib/llvm-16/bin/llvm-config");
+        let toolchain = Toolchain {
+            llvm_config: llvm_config.clone(),
+            bindir: PathBuf::from("/usr/lib/llvm-16/bin"),
+            libdir: PathBuf::from("/usr/lib/llvm-16/lib"),
+            major_version: 16,
+            cc: None,
+            cxx: None,
+        };
+        toolchain.store(&cache, "1700000000.000000001").unwrap();
+
+        let loaded = Toolchain::load(&cache, &llvm_config, "1700000000.000000001").unwrap();
+        assert_eq!(loaded.bindir, toolchain.bindir);
+        assert_eq!(loaded.libdir, toolchain.libdir);
+        assert_eq!(loaded.major_version, 16);
+        assert!(Toolchain::load(&cache, &llvm_config, "1700000000.000000002").is_none());
+        assert!(Toolchain::load(
+            &cache,
+            Path::new("/usr/bin/llvm-config"),
+            "1700000000.000000001"
+        )
+        .is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
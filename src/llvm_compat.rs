@@ -0,0 +1,215 @@
+use crate::compiler_option::CompilerOption;
+
+/// The newest LLVM release the tables below are known to be correct for.
+/// Newer releases are treated like this one.
+pub const NEWEST_KNOWN_VERSION: u32 = 19;
+
+/// `(flag, whether it selects the new pass manager)`, for the releases that
+/// still have both pass managers.
+const PASS_MANAGER_FLAGS: &[(&str, bool)] = &[
+    ("-fexperimental-new-pass-manager", true),
+    ("-fno-experimental-new-pass-manager", false),
+    ("-flegacy-pass-manager", false),
+    ("-fno-legacy-pass-manager", true),
+];
+
+/// The behavior of a range of LLVM releases, starting at `since`.
+#[derive(Debug)]
+pub struct Capabilities {
+    pub since: u32,
+    /// Whether the new pass manager is the default one. Plugins are loaded
+    /// with `-fpass-plugin=` into the new one, and with `-Xclang -load` into
+    /// the legacy one.
+    pub new_pass_manager: bool,
+    /// Whether clang has `-fpass-plugin=`. Before, plugins can only be loaded
+    /// into the legacy pass manager.
+    pub pass_plugin_flag: bool,
+    /// Whether `-fsanitize=function` identifies functions by a type hash,
+    /// which works without RTTI and in C, rather than by their RTTI.
+    pub function_sanitizer_type_hash: bool,
+}
+
+/// Sorted by `since`.
+const CAPABILITIES: &[Capabilities] = &[
+    Capabilities {
+        since: 1,
+        new_pass_manager: false,
+        pass_plugin_flag: false,
+        function_sanitizer_type_hash: false,
+    },
+    Capabilities {
+        since: 11,
+        new_pass_manager: false,
+        pass_plugin_flag: true,
+        function_sanitizer_type_hash: false,
+    },
+    // the new pass manager became the default in LLVM 13
+    Capabilities {
+        since: 13,
+        new_pass_manager: true,
+        pass_plugin_flag: true,
+        function_sanitizer_type_hash: false,
+    },
+    Capabilities {
+        since: 17,
+        new_pass_manager: true,
+        pass_plugin_flag: true,
+        function_sanitizer_type_hash: true,
+    },
+];
+
+/// `(since, old spelling, new spelling)`: clang accepts the new spelling since
+/// the given release, and the old one before it.
+const RENAMED_FLAGS: &[(u32, &str, &str)] = &[
+    (13, "-fsanitize-blacklist=", "-fsanitize-ignorelist="),
+    (
+        13,
+        "-fsanitize-coverage-whitelist=",
+        "-fsanitize-coverage-allowlist=",
+    ),
+    (
+        13,
+        "-fsanitize-coverage-blacklist=",
+        "-fsanitize-coverage-ignorelist=",
+    ),
+];
+
+/// `(release, spelling)`: clang rejects the flag since the given release.
+const REMOVED_FLAGS: &[(u32, &str)] = &[
+    (15, "-flegacy-pass-manager"),
+    (15, "-fno-experimental-new-pass-manager"),
+];
+
+/// What a given LLVM release supports, see the tables above.
+#[derive(Debug, Clone, Copy)]
+pub struct LlvmCompat {
+    /// The release whose behavior is used, `NEWEST_KNOWN_VERSION` at most.
+    pub version: u32,
+}
+
+impl LlvmCompat {
+    pub fn new(major_version: u32) -> Self {
+        LlvmCompat {
+            version: major_version.min(NEWEST_KNOWN_VERSION),
+        }
+    }
+
+    /// Whether the tables cover this release.
+    pub fn is_known(major_version: u32) -> bool {
+        major_version <= NEWEST_KNOWN_VERSION
+    }
+
+    pub fn capabilities(&self) -> &'static Capabilities {
+        CAPABILITIES
+            .iter()
+            .rev()
+            .find(|capabilities| capabilities.since <= self.version)
+            .unwrap_or(&CAPABILITIES[0])
+    }
+
+    /// Whether the command runs the new pass manager: the last pass manager
+    /// flag the release accepts, or its default.
+    pub fn uses_new_pass_manager(&self, options: &[CompilerOption]) -> bool {
+        options
+            .iter()
+            .rev()
+            .filter(|opt| opt.is_enabled && !self.is_removed(&opt.name))
+            .find_map(|opt| {
+                PASS_MANAGER_FLAGS
+                    .iter()
+                    .find(|(flag, _)| opt.name == *flag)
+                    .map(|&(_, new)| new)
+            })
+            .unwrap_or(self.capabilities().new_pass_manager)
+    }
+
+    /// The options loading a pass plugin into the pass manager of the command,
+    /// or into the legacy one if clang has no `-fpass-plugin=` yet.
+    pub fn pass_plugin_options(
+        &self,
+        plugin: &str,
+        options: &[CompilerOption],
+    ) -> Vec<CompilerOption> {
+        if self.capabilities().pass_plugin_flag && self.uses_new_pass_manager(options) {
+            vec![CompilerOption::new_with_value(
+                "-fpass-plugin=",
+                vec![plugin],
+            )]
+        } else {
+            vec![
+                CompilerOption::new_with_value("-Xclang", vec!["-load"]),
+                CompilerOption::new_with_value("-Xclang", vec![plugin]),
+            ]
+        }
+    }
+
+    /// The spelling this release uses for a renamed flag, or `None` if the
+    /// flag is not renamed or already spelled right.
+    pub fn renamed_spelling(&self, spelling: &str) -> Option<&'static str> {
+        RENAMED_FLAGS.iter().find_map(|&(since, old, new)| {
+            if self.version >= since && spelling == old {
+                Some(new)
+            } else if self.version < since && spelling == new {
+                Some(old)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Whether this release rejects the flag.
+    pub fn is_removed(&self, spelling: &str) -> bool {
+        REMOVED_FLAGS
+            .iter()
+            .any(|&(since, removed)| self.version >= since && spelling == removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_table::parse_args;
+
+    fn plugin_args(version: u32, args: &[&str]) -> Vec<String> {
+        LlvmCompat::new(version)
+            .pass_plugin_options("pass.so", &parse_args(args))
+            .iter()
+            .flat_map(|option| option.to_args())
+            .collect()
+    }
+
+    #[test]
+    fn plugins_follow_the_default_pass_manager() {
+        assert_eq!(
+            plugin_args(12, &[]),
+            ["-Xclang", "-load", "-Xclang", "pass.so"]
+        );
+        assert_eq!(plugin_args(13, &[]), ["-fpass-plugin=pass.so"]);
+    }
+
+    #[test]
+    fn plugins_follow_the_pass_manager_of_the_command() {
+        assert_eq!(
+            plugin_args(12, &["-fexperimental-new-pass-manager"]),
+            ["-fpass-plugin=pass.so"]
+        );
+        assert_eq!(
+            plugin_args(14, &["-fno-legacy-pass-manager", "-flegacy-pass-manager"]),
+            ["-Xclang", "-load", "-Xclang", "pass.so"]
+        );
+        // clang has no -fpass-plugin= before LLVM 11
+        assert_eq!(
+            plugin_args(10, &["-fexperimental-new-pass-manager"]),
+            ["-Xclang", "-load", "-Xclang", "pass.so"]
+        );
+        assert_eq!(
+            plugin_args(11, &["-fexperimental-new-pass-manager"]),
+            ["-fpass-plugin=pass.so"]
+        );
+        // removed in LLVM 15, where only the new pass manager is left
+        assert_eq!(
+            plugin_args(15, &["-flegacy-pass-manager"]),
+            ["-fpass-plugin=pass.so"]
+        );
+    }
+}
@@ -3,66 +3,159 @@ use std::io::Write;
 use std::os::unix::process::ExitStatusExt;
 
 use argus::{
+    compiler_option::OptionManagement,
+    context::{Config, VisitorContext},
     env::*,
-    llvm::{get_clang_path, get_clang_plus_plus_path},
+    error::{report, ArgusError, ErrorPolicy},
+    llvm::{fallback_compiler, Toolchain},
+    llvm_compat::{LlvmCompat, NEWEST_KNOWN_VERSION},
+    object::default_search_paths,
     option_manager::CompilerOptionManager,
-    option_visitors::{
-        AdditionalObjectsVisitor, AdditionalPassesVisitor, DefaultOptimizationVisitor,
-        DefaultParametersVisitor, LibfuzzerVisitor, OptionVisitor, ProfileVisitor, RuntimeVisitor,
-        SanitizerVisitor, XVisitor,
-    },
+    option_visitors::VisitorRegistry,
 };
 
 use colored::*;
 
 fn main() {
-    let program_name = std::env::args().next().unwrap();
+    let program_name = std::env::args().next().unwrap_or_default();
     let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(error) => {
+            report("error", "config", &error);
+            std::process::exit(1);
+        }
+    };
+    let policy = match ErrorPolicy::from_config(&config) {
+        Ok(policy) => policy,
+        Err(error) => {
+            report("error", "config", &error);
+            std::process::exit(1);
+        }
+    };
+
+    let mut manager = CompilerOptionManager::new(args.clone());
+
+    let toolchain = match Toolchain::detect(&config, &program_name) {
+        Ok(toolchain) => toolchain,
+        // without a toolchain no visitor can run, so `warn` skips them all
+        Err(error) => match policy {
+            ErrorPolicy::Fail => {
+                report("error", "toolchain", &error);
+                std::process::exit(1);
+            }
+            ErrorPolicy::WarnAndSkip | ErrorPolicy::PlainCompile => {
+                report("warn", "toolchain", &error);
+                let compiler = fallback_compiler(&config, &program_name);
+                execute(
+                    &compiler.to_string_lossy(),
+                    &args,
+                    config.is_set(ARGUS_DEBUG),
+                );
+            }
+        },
+    };
+    if !LlvmCompat::is_known(toolchain.major_version) {
+        let error = ArgusError::UnknownLlvmVersion {
+            version: toolchain.major_version,
+            assumed: NEWEST_KNOWN_VERSION,
+        };
+        report("warn", "toolchain", &error);
+    }
+    let object_search_paths = default_search_paths(&config);
+    let context = VisitorContext {
+        mode: manager.options.compile_mode(),
+        config,
+        program_name,
+        toolchain,
+        object_search_paths,
+    };
+
+    let visitors = match VisitorRegistry::with_builtin_visitors().select(&context.config) {
+        Ok(visitors) => visitors,
+        Err(error) => {
+            report("error", "registry", &error);
+            std::process::exit(1);
+        }
+    };
 
-    let mut manager = CompilerOptionManager::new(args);
-
-    let mut visitors: Vec<Box<dyn OptionVisitor>> = vec![
-        Box::<DefaultParametersVisitor>::default(),
-        Box::<DefaultOptimizationVisitor>::default(),
-        Box::<SanitizerVisitor>::default(),
-        Box::<XVisitor>::default(),
-    ];
+    let explain = context.config.is_set(ARGUS_EXPLAIN);
 
-    // Optional visitors
-    if std::env::var(ADD_DRIVER).is_ok() {
-        visitors.push(Box::<LibfuzzerVisitor>::default());
-    }
-    if std::env::var(ADD_RUNTIME).is_ok() {
-        visitors.push(Box::<RuntimeVisitor>::default());
-    }
-    if std::env::var(PROFILING).is_ok() {
-        visitors.push(Box::<ProfileVisitor>::default());
-    }
-    if std::env::var(ADD_ADDITIONAL_PASSES).is_ok() {
-        visitors.push(Box::<AdditionalPassesVisitor>::default());
-    }
-    if std::env::var(ADD_ADDITIONAL_OBJECTS).is_ok() {
-        visitors.push(Box::<AdditionalObjectsVisitor>::default());
+    for mut visitor in visitors {
+        match manager.visit(visitor.as_mut(), &context) {
+            Ok(diff) => {
+                if explain {
+                    eprintln!("[{}::explain] {}", "ARGUS".italic().bold(), diff);
+                }
+            }
+            Err(error) => match policy {
+                ErrorPolicy::Fail => {
+                    report("error", visitor.name(), &error);
+                    std::process::exit(1);
+                }
+                ErrorPolicy::WarnAndSkip => report("warn", visitor.name(), &error),
+                ErrorPolicy::PlainCompile => {
+                    report("warn", visitor.name(), &error);
+                    manager = CompilerOptionManager::new(args);
+                    break;
+                }
+            },
+        }
     }
 
-    for mut visitor in visitors {
-        visitor.visit(&mut manager.options);
-        manager.cleanup();
+    if explain {
+        eprintln!("[{}::explain] final command:", "ARGUS".italic().bold());
+        for option in &manager.options {
+            let origin = option
+                .provenance
+                .origin
+                .as_ref()
+                .map_or_else(|| String::from("unknown"), |origin| origin.to_string());
+            let events: Vec<String> = option
+                .provenance
+                .events
+                .iter()
+                .map(|event| event.to_string())
+                .collect();
+            if events.is_empty() {
+                eprintln!(
+                    "  {} {}",
+                    option.to_args().join(" "),
+                    format!("<- {}", origin).dimmed()
+                );
+            } else {
+                eprintln!(
+                    "  {} {}",
+                    option.to_args().join(" "),
+                    format!("<- {}; {}", origin, events.join("; ")).dimmed()
+                );
+            }
+        }
     }
 
     // For debugging purposes, print the command to the console
-    let compiler = if program_name.ends_with("++") {
-        get_clang_plus_plus_path()
+    let compiler = match if context.is_cxx() {
+        context.toolchain.clang_plus_plus()
     } else {
-        get_clang_path()
-    }
-    .expect("Could not find clang or clang++")
-    .to_string_lossy()
-    .to_string();
+        context.toolchain.clang()
+    } {
+        Ok(compiler) => compiler.to_string_lossy().to_string(),
+        Err(error) => {
+            report("error", "toolchain", &error);
+            std::process::exit(1);
+        }
+    };
 
-    let debug = std::env::var(ARGUS_DEBUG).is_ok();
+    let debug = context.config.is_set(ARGUS_DEBUG);
 
     if debug {
+        eprintln!(
+            "[{}::toolchain] {} {}",
+            "ARGUS".italic().bold(),
+            context.toolchain,
+            format!("-> {}", compiler).dimmed()
+        );
+
         eprintln!(
             "[{}::in ] {}",
             "ARGUS".italic().bold(),
@@ -88,20 +181,42 @@ fn main() {
             manager
         );
 
-        let mut file = OpenOptions::new()
+        // the log is best effort, it must not break the build
+        if let Err(source) = OpenOptions::new()
             .create(true)
             .append(true)
             .open(log_path)
-            .expect("Could not open log file");
-
-        writeln!(file, "{}", log_entry).expect("Could not write to log file");
+            .and_then(|mut file| writeln!(file, "{}", log_entry))
+        {
+            let error = ArgusError::Io {
+                path: log_path.to_path_buf(),
+                source,
+            };
+            report("warn", "log", &error);
+        }
     }
 
+    execute(&compiler, &manager.get_command(), debug);
+}
+
+/// Run the compiler and exit with its exit code.
+fn execute(compiler: &str, args: &[String], debug: bool) -> ! {
     // Execute the command
-    let result = std::process::Command::new(compiler)
-        .args(manager.get_command())
-        .status()
-        .expect("Failed to execute command");
+    let result = match std::process::Command::new(compiler).args(args).status() {
+        Ok(result) => result,
+        Err(source) => {
+            let error = ArgusError::Io {
+                path: compiler.into(),
+                source,
+            };
+            report("error", "exec", &error);
+            std::process::exit(1);
+        }
+    };
+    // killed by a signal: report it like a shell does
+    let exit_code = result
+        .code()
+        .unwrap_or_else(|| 128 + result.signal().unwrap_or(0));
 
     if debug {
         if result == std::process::ExitStatus::from_raw(0) {
@@ -110,11 +225,11 @@ fn main() {
             eprintln!(
                 "[{}::exec] {}",
                 "ARGUS".italic().bold(),
-                format!("Exit code: {}", result.code().unwrap()).red()
+                format!("Exit code: {}", exit_code).red()
             );
         }
     }
 
     // return the exit code
-    std::process::exit(result.code().unwrap());
+    std::process::exit(exit_code);
 }
@@ -1,72 +1,565 @@
 use std::env;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-/// Try our best to find the object file.
-/// We'll try the following methods:
-/// 0. Check the object file path directly.
-/// 1. Check the argv[0] directory.
-/// 2. Check /proc/self/exe (Linux specific).
-/// 3. Use a predefined, hardcoded path.
-/// 4. Try the current directory.
-/// 5. Try the / (root) directory.
-pub fn find_object(obj: &str) -> Option<PathBuf> {
-    // Check the object file path directly
-    if Path::new(obj).exists() {
-        return Some(PathBuf::from(obj));
-    }
-
-    // Get the object file name
-    let obj = &Path::new(obj)
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
-
-    // Check the argv[0] directory
+use crate::{
+    compiler_option::CompilerOption, context::Config, embedded, env::ARGUS_OBJECT_PATH,
+    error::ArgusError, target::Arch,
+};
+
+/// The directories `find_object` searches, in order:
+/// 1. The `ARGUS_OBJECT_PATH` environment variable, colon separated.
+/// 2. The `ARGUS_OBJECT_PATH` entry of the config file, colon separated.
+/// 3. The argv[0] directory.
+/// 4. The /proc/self/exe directory and its ../bin (Linux specific).
+pub fn default_search_paths(config: &Config) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    if let Some(list) = config.get_from_env(ARGUS_OBJECT_PATH) {
+        paths.extend(env::split_paths(list).filter(|dir| !dir.as_os_str().is_empty()));
+    }
+    if let Some(list) = config.get_from_file(ARGUS_OBJECT_PATH) {
+        paths.extend(env::split_paths(list).filter(|dir| !dir.as_os_str().is_empty()));
+    }
+
     if let Some(argv0) = env::args().next() {
         if let Some(parent) = Path::new(&argv0).parent() {
-            let path = parent.join(obj);
-            if path.exists() {
-                return Some(path);
+            if !parent.as_os_str().is_empty() {
+                paths.push(parent.to_path_buf());
             }
         }
     }
 
-    // Check the /proc/self/exe path
-    let proc_path = PathBuf::from("/proc/self/exe");
-    if let Ok(exe_path) = fs::read_link(proc_path) {
+    if let Ok(exe_path) = fs::read_link("/proc/self/exe") {
         if let Some(parent) = exe_path.parent() {
-            let path = parent.join(obj);
-            if path.exists() {
-                return Some(path);
+            paths.push(parent.to_path_buf());
+            paths.push(parent.join("../bin"));
+        }
+    }
+
+    let mut unique = Vec::new();
+    for path in paths {
+        if !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    unique
+}
+
+/// Find the object file. A name containing a `/` is a path and used as is,
+/// any other name is searched in each of the search paths, and else
+/// extracted from the objects embedded in Argus.
+pub fn find_object(obj: &str, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    if obj.contains('/') {
+        return Some(PathBuf::from(obj)).filter(|path| path.exists());
+    }
+
+    search_paths
+        .iter()
+        .map(|dir| dir.join(obj))
+        .find(|path| path.exists())
+        .or_else(|| embedded::extract(obj))
+}
+
+/// Find an object with `find_object` and return its canonical path. `what`
+/// describes the object in the error message, e.g. "runtime object".
+pub fn locate_object(
+    what: &'static str,
+    obj: &str,
+    search_paths: &[PathBuf],
+) -> Result<String, ArgusError> {
+    let path = find_object(obj, search_paths).ok_or_else(|| ArgusError::ObjectNotFound {
+        what,
+        name: obj.to_string(),
+        searched: if obj.contains('/') {
+            vec![PathBuf::from(obj)]
+        } else {
+            search_paths.iter().map(|dir| dir.join(obj)).collect()
+        },
+    })?;
+    let path = path
+        .canonicalize()
+        .map_err(|source| ArgusError::Io { path, source })?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// The names of the variants of an object for an architecture, in the order
+/// they are tried: `bandfuzz-rt.o` for i386 is `bandfuzz-rt-i386.o`. The plain
+/// name is only used for the host architecture.
+pub fn variant_names(obj: &str, arch: &Arch) -> Vec<String> {
+    let (stem, extension) = match obj.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (obj, String::new()),
+    };
+    let mut names = vec![format!("{}-{}{}", stem, arch, extension)];
+    if *arch == Arch::host() {
+        names.push(obj.to_string());
+    }
+    names
+}
+
+/// Find the variant of an object for an architecture, see `variant_names`.
+/// Paths (names containing a `/`) are used as is, whatever the architecture.
+pub fn locate_object_variant(
+    what: &'static str,
+    obj: &str,
+    arch: &Arch,
+    search_paths: &[PathBuf],
+) -> Result<String, ArgusError> {
+    if obj.contains('/') {
+        return locate_object(what, obj, search_paths);
+    }
+    let names = variant_names(obj, arch);
+    match names
+        .iter()
+        .find(|name| find_object(name, search_paths).is_some())
+    {
+        Some(name) => locate_object(what, name, search_paths),
+        None => Err(ArgusError::ObjectVariantNotFound {
+            what,
+            arch: arch.clone(),
+            names,
+            searched: search_paths.to_vec(),
+        }),
+    }
+}
+
+/// Why an object cannot be linked into the command.
+#[derive(Debug)]
+pub enum ObjectProblem {
+    /// LLVM bitcode needs `-flto` to be linked.
+    BitcodeWithoutLto,
+    NotElf,
+    /// An ELF file that is not an object or a shared library, e.g. an executable.
+    NotLinkable,
+    /// The object is built for `found`, the command for `expected`.
+    WrongArch {
+        found: String,
+        expected: Arch,
+    },
+    /// A symbol the object has to define is missing, e.g. after stripping.
+    MissingSymbol(&'static str),
+    Malformed,
+}
+
+impl fmt::Display for ObjectProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectProblem::BitcodeWithoutLto => {
+                write!(f, "LLVM bitcode can only be linked with -flto")
+            }
+            ObjectProblem::NotElf => write!(f, "not an ELF object or archive"),
+            ObjectProblem::NotLinkable => write!(f, "ELF file is not an object"),
+            ObjectProblem::WrongArch { found, expected } => write!(
+                f,
+                "object is built for {}, but the command targets {}",
+                found, expected
+            ),
+            ObjectProblem::MissingSymbol(symbol) => {
+                write!(f, "object does not define `{}`, is it stripped?", symbol)
             }
+            ObjectProblem::Malformed => write!(f, "truncated or malformed ELF file"),
+        }
+    }
+}
+
+/// Check that an object can be linked into the command, before the linker
+/// fails with a less helpful message: the file must be an ELF object (or an
+/// archive, or bitcode with `-flto`) for the target architecture that defines
+/// all symbols in `provides`.
+pub fn validate_object(
+    path: &Path,
+    options: &[CompilerOption],
+    provides: &[&'static str],
+) -> Result<(), ArgusError> {
+    let data = fs::read(path).map_err(|source| ArgusError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    check_object(&data, options, provides).map_err(|problem| ArgusError::InvalidObject {
+        path: path.to_path_buf(),
+        problem,
+    })
+}
 
-            let path = parent.join("../bin").join(obj);
-            if path.exists() {
-                return Some(path);
+fn check_object(
+    data: &[u8],
+    options: &[CompilerOption],
+    provides: &[&'static str],
+) -> Result<(), ObjectProblem> {
+    // archives are left to the linker
+    if data.starts_with(b"!<arch>\n") {
+        return Ok(());
+    }
+    // raw bitcode, or bitcode in a wrapper
+    if data.starts_with(b"BC\xC0\xDE") || data.starts_with(&[0xDE, 0xC0, 0x17, 0x0B]) {
+        let lto = options
+            .iter()
+            .any(|opt| opt.is_enabled && (opt.name == "-flto" || opt.name == "-flto="));
+        return if lto {
+            Ok(())
+        } else {
+            Err(ObjectProblem::BitcodeWithoutLto)
+        };
+    }
+
+    let elf = Elf::parse(data).ok_or(ObjectProblem::NotElf)?;
+    // ET_REL or ET_DYN
+    if elf.file_type != 1 && elf.file_type != 3 {
+        return Err(ObjectProblem::NotLinkable);
+    }
+
+    let expected = Arch::from_options(options);
+    if let Some(identity) = expected.elf_identity() {
+        if identity != (elf.class, elf.machine) {
+            return Err(ObjectProblem::WrongArch {
+                found: elf.arch_name(),
+                expected,
+            });
+        }
+    }
+
+    if !provides.is_empty() {
+        let defined = elf.defined_symbols().ok_or(ObjectProblem::Malformed)?;
+        if let Some(symbol) = provides
+            .iter()
+            .find(|symbol| !defined.iter().any(|defined| defined == *symbol))
+        {
+            return Err(ObjectProblem::MissingSymbol(symbol));
+        }
+    }
+    Ok(())
+}
+
+/// Just enough of an ELF reader to validate objects.
+struct Elf<'a> {
+    data: &'a [u8],
+    class: u8,
+    little_endian: bool,
+    file_type: u16,
+    machine: u16,
+}
+
+impl<'a> Elf<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if !data.starts_with(b"\x7fELF") || data.len() < 0x34 {
+            return None;
+        }
+        let mut elf = Elf {
+            data,
+            class: data[4],
+            little_endian: data[5] == 1,
+            file_type: 0,
+            machine: 0,
+        };
+        if elf.class != 1 && elf.class != 2 {
+            return None;
+        }
+        elf.file_type = elf.u16_at(0x10)?;
+        elf.machine = elf.u16_at(0x12)?;
+        Some(elf)
+    }
+
+    fn arch_name(&self) -> String {
+        let name = match (self.class, self.machine) {
+            (2, 62) => "x86_64",
+            (1, 62) => "x32",
+            (1, 3) => "i386",
+            (2, 183) => "aarch64",
+            (1, 40) => "arm",
+            (2, 243) => "riscv64",
+            (1, 243) => "riscv32",
+            _ => "",
+        };
+        if name.is_empty() {
+            format!(
+                "ELF machine {} ({}-bit)",
+                self.machine,
+                if self.class == 2 { 64 } else { 32 }
+            )
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.data
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64_at(&self, offset: usize) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// A word-sized field, 4 bytes in 32-bit and 8 bytes in 64-bit ELF.
+    fn word_at(&self, offset: usize) -> Option<usize> {
+        if self.class == 2 {
+            self.u64_at(offset)?.try_into().ok()
+        } else {
+            self.u32_at(offset)?.try_into().ok()
+        }
+    }
+
+    /// `(type, offset, size, link)` of every section header.
+    fn sections(&self) -> Option<Vec<(u32, usize, usize, usize)>> {
+        let (shoff, shentsize, shnum) = if self.class == 2 {
+            (self.word_at(0x28)?, self.u16_at(0x3A)?, self.u16_at(0x3C)?)
+        } else {
+            (self.word_at(0x20)?, self.u16_at(0x2E)?, self.u16_at(0x30)?)
+        };
+        // with extended numbering, the count is the `sh_size` of section 0
+        let shnum = match shnum {
+            0 if shoff == 0 => 0,
+            0 if self.class == 2 => self.word_at(shoff.checked_add(0x20)?)?,
+            0 => self.word_at(shoff.checked_add(0x14)?)?,
+            shnum => shnum as usize,
+        };
+        (0..shnum)
+            .map(|index| {
+                let header = shoff.checked_add(index.checked_mul(shentsize as usize)?)?;
+                Some(if self.class == 2 {
+                    (
+                        self.u32_at(header + 0x04)?,
+                        self.word_at(header + 0x18)?,
+                        self.word_at(header + 0x20)?,
+                        self.u32_at(header + 0x28)? as usize,
+                    )
+                } else {
+                    (
+                        self.u32_at(header + 0x04)?,
+                        self.word_at(header + 0x10)?,
+                        self.word_at(header + 0x14)?,
+                        self.u32_at(header + 0x18)? as usize,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Names of the global and weak symbols defined in `.symtab`, or
+    /// `.dynsym` for stripped shared libraries.
+    fn defined_symbols(&self) -> Option<Vec<&'a str>> {
+        // SHT_SYMTAB, SHT_DYNSYM
+        const SYMTAB: u32 = 2;
+        const DYNSYM: u32 = 11;
+
+        let sections = self.sections()?;
+        let Some(&(_, offset, size, link)) = sections
+            .iter()
+            .find(|section| section.0 == SYMTAB)
+            .or_else(|| sections.iter().find(|section| section.0 == DYNSYM))
+        else {
+            return Some(Vec::new());
+        };
+        let &(_, strtab, strtab_size, _) = sections.get(link)?;
+        let strings = self.data.get(strtab..strtab.checked_add(strtab_size)?)?;
+
+        let entry_size = if self.class == 2 { 24 } else { 16 };
+        let mut symbols = Vec::new();
+        for entry in (offset..offset.checked_add(size)?).step_by(entry_size) {
+            let name = self.u32_at(entry)? as usize;
+            let (info, section_index) = if self.class == 2 {
+                (*self.data.get(entry + 4)?, self.u16_at(entry + 6)?)
+            } else {
+                (*self.data.get(entry + 12)?, self.u16_at(entry + 14)?)
+            };
+            // STB_GLOBAL, STB_WEAK and STB_GNU_UNIQUE; local symbols are not
+            // visible to other objects
+            let exported = matches!(info >> 4, 1 | 2 | 10);
+            // SHN_UNDEF
+            if section_index == 0 || name == 0 || !exported {
+                continue;
             }
+            let name = strings.get(name..)?;
+            let end = name.iter().position(|&b| b == 0)?;
+            if let Ok(name) = std::str::from_utf8(&name[..end]) {
+                symbols.push(name);
+            }
+        }
+        Some(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_path_comes_first_and_duplicates_are_dropped() {
+        let config = Config::from_vars([(
+            ARGUS_OBJECT_PATH.to_string(),
+            String::from("/env/a::/env/b:/env/a"),
+        )]);
+        let paths = default_search_paths(&config);
+        assert_eq!(
+            paths[..2],
+            [PathBuf::from("/env/a"), PathBuf::from("/env/b")]
+        );
+        assert_eq!(paths.iter().filter(|dir| dir.ends_with("a")).count(), 1);
+    }
+
+    #[test]
+    fn names_are_searched_and_paths_are_not() {
+        let dir = env::temp_dir().join(format!("argus-object-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("rt.o"), "").unwrap();
+        let search_paths = [PathBuf::from("/nonexistent"), dir.clone()];
+
+        assert_eq!(find_object("rt.o", &search_paths), Some(dir.join("rt.o")));
+        assert_eq!(find_object("./rt.o", &search_paths), None);
+        match locate_object("runtime object", "missing.o", &search_paths) {
+            Err(ArgusError::ObjectNotFound { searched, .. }) => assert_eq!(
+                searched,
+                [
+                    PathBuf::from("/nonexistent/missing.o"),
+                    dir.join("missing.o")
+                ]
+            ),
+            result => panic!("unexpected {:?}", result),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_plain_name_is_only_used_for_the_host() {
+        let host = Arch::host();
+        assert_eq!(
+            variant_names("bandfuzz-rt.o", &host),
+            [
+                format!("bandfuzz-rt-{}.o", host),
+                String::from("bandfuzz-rt.o")
+            ]
+        );
+        let other = Arch::Other(String::from("sparc"));
+        assert_eq!(variant_names("libdriver", &other), ["libdriver-sparc"]);
+    }
+
+    #[test]
+    fn a_missing_variant_lists_the_names_tried() {
+        let dir = env::temp_dir().join(format!("argus-variant-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("rt.o"), "").unwrap();
+        fs::write(dir.join("rt-sparc.o"), "").unwrap();
+        let search_paths = [dir.clone()];
+
+        let sparc = Arch::Other(String::from("sparc"));
+        let found = locate_object_variant("runtime object", "rt.o", &sparc, &search_paths);
+        assert!(found.unwrap().ends_with("/rt-sparc.o"));
+        let mips = Arch::Other(String::from("mips"));
+        match locate_object_variant("runtime object", "rt.o", &mips, &search_paths) {
+            Err(ArgusError::ObjectVariantNotFound { names, .. }) => {
+                assert_eq!(names, ["rt-mips.o"])
+            }
+            result => panic!("unexpected {:?}", result),
         }
+        let _ = fs::remove_dir_all(&dir);
     }
 
-    // Check the predefined path
-    let predefined_bandfuzz_path = "/workspaces/BANDFUZZplusplus/bin";
-    let path = Path::new(predefined_bandfuzz_path).join(obj);
-    if path.exists() {
-        return Some(path);
+    /// A 64-bit little endian x86_64 relocatable object with `.strtab` and
+    /// `.symtab`, the section count in section 0 if `extended` is set.
+    fn object(extended: bool) -> Vec<u8> {
+        let strings = b"\0local_sym\0global_sym\0weak_sym\0undefined_sym\0";
+        // (name offset, st_info, st_shndx)
+        let symbols: &[(u32, u8, u16)] = &[
+            (0, 0, 0),
+            (1, 0x00, 1),
+            (11, 0x10, 1),
+            (22, 0x20, 1),
+            (31, 0x10, 0),
+        ];
+
+        let strtab = 0x40;
+        let symtab = strtab + strings.len().next_multiple_of(8);
+        let shoff = symtab + symbols.len() * 24;
+        let mut data = vec![0u8; shoff + 3 * 64];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[6] = 1;
+        data[0x10..0x12].copy_from_slice(&1u16.to_le_bytes());
+        data[0x12..0x14].copy_from_slice(&62u16.to_le_bytes());
+        data[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        data[0x3A..0x3C].copy_from_slice(&64u16.to_le_bytes());
+        let shnum: u16 = if extended { 0 } else { 3 };
+        data[0x3C..0x3E].copy_from_slice(&shnum.to_le_bytes());
+
+        data[strtab..strtab + strings.len()].copy_from_slice(strings);
+        for (index, &(name, info, shndx)) in symbols.iter().enumerate() {
+            let entry = symtab + index * 24;
+            data[entry..entry + 4].copy_from_slice(&name.to_le_bytes());
+            data[entry + 4] = info;
+            data[entry + 6..entry + 8].copy_from_slice(&shndx.to_le_bytes());
+        }
+
+        // (type, offset, size, link)
+        let sections: [(u32, usize, usize, u32); 3] = [
+            (0, 0, if extended { 3 } else { 0 }, 0),
+            (3, strtab, strings.len(), 0),
+            (2, symtab, symbols.len() * 24, 1),
+        ];
+        for (index, (kind, offset, size, link)) in sections.into_iter().enumerate() {
+            let header = shoff + index * 64;
+            data[header + 0x04..header + 0x08].copy_from_slice(&kind.to_le_bytes());
+            data[header + 0x18..header + 0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+            data[header + 0x20..header + 0x28].copy_from_slice(&(size as u64).to_le_bytes());
+            data[header + 0x28..header + 0x2C].copy_from_slice(&link.to_le_bytes());
+        }
+        data
     }
 
-    // Try the current directory
-    let path = Path::new(".").join(obj);
-    if path.exists() {
-        return Some(path);
+    #[test]
+    fn defined_symbols_are_global_or_weak() {
+        let data = object(false);
+        let elf = Elf::parse(&data).unwrap();
+        assert_eq!(elf.defined_symbols().unwrap(), ["global_sym", "weak_sym"]);
     }
 
-    // Try the / (root) directory
-    let path = Path::new("/").join(obj);
-    if path.exists() {
-        return Some(path);
+    #[test]
+    fn extended_section_numbering() {
+        let data = object(true);
+        let elf = Elf::parse(&data).unwrap();
+        assert_eq!(elf.sections().unwrap().len(), 3);
+        assert_eq!(elf.defined_symbols().unwrap(), ["global_sym", "weak_sym"]);
     }
 
-    None
+    #[test]
+    fn local_symbols_do_not_satisfy_provides() {
+        let path = std::env::temp_dir().join(format!("argus-test-{}.o", std::process::id()));
+        std::fs::write(&path, object(false)).unwrap();
+        let result = validate_object(&path, &[], &["local_sym"]);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(
+            result,
+            Err(ArgusError::InvalidObject {
+                problem: ObjectProblem::MissingSymbol("local_sym"),
+                ..
+            })
+        ));
+    }
 }
@@ -4,7 +4,14 @@ use std::{
     path::PathBuf,
 };
 
-use crate::compiler_option::CompilerOption;
+use crate::{
+    compiler_option::CompilerOption,
+    context::VisitorContext,
+    error::ArgusError,
+    option_table::parse_args,
+    option_visitors::OptionVisitor,
+    provenance::{OptionEvent, OptionOrigin, VisitDiff},
+};
 
 pub struct CompilerOptionManager {
     pub options: Vec<CompilerOption>,
@@ -12,29 +19,120 @@ pub struct CompilerOptionManager {
 
 impl CompilerOptionManager {
     pub fn new(args: Vec<String>) -> Self {
-        let options = args
-            .iter()
-            .flat_map(|arg| {
-                if let Some(response_file_path) = arg.strip_prefix('@') {
-                    match read_response_file(PathBuf::from(response_file_path)) {
-                        Ok(response_options) => response_options,
-                        Err(_) => vec![],
-                    }
-                } else {
-                    vec![CompilerOption::from_arg(arg)]
-                }
+        // Expand response files first, so an option and its argument can be
+        // matched even if they come from different places.
+        let mut expanded_args = Vec::new();
+        let mut origins = Vec::new();
+        for (index, arg) in args.into_iter().enumerate() {
+            // argv[0] is the compiler
+            let argument = index + 1;
+            if let Some(response_file_path) = arg.strip_prefix('@') {
+                let response_args =
+                    read_response_file(PathBuf::from(response_file_path)).unwrap_or_default();
+                let origin = OptionOrigin::ResponseFile {
+                    path: response_file_path.to_string(),
+                    argument,
+                };
+                origins.extend(std::iter::repeat_n(origin, response_args.len()));
+                expanded_args.extend(response_args);
+            } else {
+                origins.push(OptionOrigin::Argument(argument));
+                expanded_args.push(arg);
+            }
+        }
+
+        // An option starts at the argument it was parsed from.
+        let mut options = parse_args(&expanded_args);
+        let mut position = 0;
+        for option in &mut options {
+            option.provenance.origin = origins.get(position).cloned();
+            position += option.to_args().len();
+        }
+        CompilerOptionManager { options }
+    }
+
+    /// Run a visitor, record what it changed on each option and remove the
+    /// disabled options afterwards. If the visitor fails, its changes are undone.
+    pub fn visit(
+        &mut self,
+        visitor: &mut dyn OptionVisitor,
+        context: &VisitorContext,
+    ) -> Result<VisitDiff, ArgusError> {
+        let name = visitor.name();
+        let snapshot = self.options.clone();
+        let before: Vec<(String, bool)> = self
+            .options
+            .iter_mut()
+            .enumerate()
+            .map(|(slot, option)| {
+                option.provenance.slot = Some(slot);
+                (option.to_args().join(" "), option.is_enabled)
             })
             .collect();
-        CompilerOptionManager { options }
+
+        if let Err(error) = visitor.visit(context, &mut self.options) {
+            self.options = snapshot;
+            return Err(error);
+        }
+
+        let mut diff = VisitDiff {
+            visitor: name,
+            ..Default::default()
+        };
+        let mut seen = vec![false; before.len()];
+        for option in &mut self.options {
+            let after = option.to_args().join(" ");
+            match option.provenance.slot.take() {
+                Some(slot) if !seen[slot] => {
+                    seen[slot] = true;
+                    let (before, was_enabled) = &before[slot];
+                    if *was_enabled && !option.is_enabled {
+                        option
+                            .provenance
+                            .events
+                            .push(OptionEvent::Disabled { visitor: name });
+                        diff.removed.push(before.clone());
+                    } else if !*was_enabled && option.is_enabled {
+                        option
+                            .provenance
+                            .events
+                            .push(OptionEvent::Enabled { visitor: name });
+                        diff.added.push(after);
+                    } else if option.is_enabled && *before != after {
+                        option.provenance.events.push(OptionEvent::Modified {
+                            visitor: name,
+                            before: before.clone(),
+                            after: after.clone(),
+                        });
+                        diff.modified.push((before.clone(), after));
+                    }
+                }
+                // new options, including copies of existing ones (cloning drops the slot)
+                _ => {
+                    option.provenance.origin = Some(OptionOrigin::Visitor(name));
+                    if option.is_enabled {
+                        diff.added.push(after);
+                    }
+                }
+            }
+        }
+        // options the visitor dropped from the list
+        for (slot, (before, was_enabled)) in before.into_iter().enumerate() {
+            if !seen[slot] && was_enabled {
+                diff.removed.push(before);
+            }
+        }
+
+        self.cleanup();
+        Ok(diff)
     }
 
     pub fn get_command(&self) -> Vec<String> {
-        return self
-            .options
+        self.options
             .iter()
             .filter(|option| option.is_enabled)
-            .map(|option| option.to_string())
-            .collect();
+            .flat_map(|option| option.to_args())
+            .collect()
     }
 
     pub fn cleanup(&mut self) {
@@ -54,7 +152,7 @@ pub fn check_if_option_exists(options: &[CompilerOption], name: &str) -> bool {
     options.iter().any(|o| o.name == name)
 }
 
-fn read_response_file(path: PathBuf) -> io::Result<Vec<CompilerOption>> {
+fn read_response_file(path: PathBuf) -> io::Result<Vec<String>> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
     let mut args = Vec::new();
@@ -76,8 +174,7 @@ fn read_response_file(path: PathBuf) -> io::Result<Vec<CompilerOption>> {
                 '\'' if !in_double_quote => in_single_quote = !in_single_quote,
                 ' ' | '\n' | '\t' | '\r' if !in_double_quote && !in_single_quote => {
                     if !current_arg.is_empty() {
-                        args.push(CompilerOption::from_arg(&current_arg));
-                        current_arg.clear();
+                        args.push(std::mem::take(&mut current_arg));
                     }
                 }
                 _ => current_arg.push(c),
@@ -86,8 +183,118 @@ fn read_response_file(path: PathBuf) -> io::Result<Vec<CompilerOption>> {
     }
 
     if !current_arg.is_empty() {
-        args.push(CompilerOption::from_arg(&current_arg));
+        args.push(current_arg);
     }
 
     Ok(args)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{compile_mode::CompileMode, context::Config, llvm::Toolchain};
+
+    struct FnVisitor(fn(&mut Vec<CompilerOption>));
+
+    impl OptionVisitor for FnVisitor {
+        fn name(&self) -> &'static str {
+            "FnVisitor"
+        }
+
+        fn visit(
+            &mut self,
+            _context: &VisitorContext,
+            options: &mut Vec<CompilerOption>,
+        ) -> Result<(), ArgusError> {
+            (self.0)(options);
+            Ok(())
+        }
+    }
+
+    fn visit(
+        args: &[&str],
+        edit: fn(&mut Vec<CompilerOption>),
+    ) -> (CompilerOptionManager, VisitDiff) {
+        let context = VisitorContext {
+            config: Config::default(),
+            program_name: String::from("argus"),
+            toolchain: Toolchain {
+                llvm_config: PathBuf::from("llvm-config"),
+                bindir: PathBuf::new(),
+                libdir: PathBuf::new(),
+                major_version: 17,
+                cc: None,
+                cxx: None,
+            },
+            object_search_paths: Vec::new(),
+            mode: CompileMode::CompileOnly,
+        };
+        let mut manager =
+            CompilerOptionManager::new(args.iter().map(|arg| arg.to_string()).collect());
+        let diff = manager.visit(&mut FnVisitor(edit), &context).unwrap();
+        (manager, diff)
+    }
+
+    #[test]
+    fn a_modified_copy_is_added() {
+        let (manager, diff) = visit(&["-std=c11", "-c", "a.c"], |options| {
+            let mut copy = options[0].clone();
+            copy.values = vec![String::from("c17")];
+            options.insert(0, copy);
+        });
+        assert_eq!(manager.get_command(), ["-std=c17", "-std=c11", "-c", "a.c"]);
+        assert_eq!(diff.added, ["-std=c17"]);
+        assert!(diff.modified.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(
+            manager.options[0].provenance.origin,
+            Some(OptionOrigin::Visitor("FnVisitor"))
+        );
+        assert_eq!(
+            manager.options[1].provenance.origin,
+            Some(OptionOrigin::Argument(1))
+        );
+        assert!(manager.options[1].provenance.events.is_empty());
+    }
+
+    #[test]
+    fn a_re_added_option_keeps_its_identity() {
+        let (manager, diff) = visit(&["-std=c11", "-c", "a.c"], |options| {
+            let option = options.remove(0);
+            options.push(option);
+        });
+        assert_eq!(manager.get_command(), ["-c", "a.c", "-std=c11"]);
+        assert!(diff.is_empty());
+        assert_eq!(
+            manager.options[2].provenance.origin,
+            Some(OptionOrigin::Argument(1))
+        );
+
+        let (manager, diff) = visit(&["-std=c11", "-c", "a.c"], |options| {
+            let mut option = options.remove(0);
+            option.values = vec![String::from("c17")];
+            options.push(option);
+        });
+        assert_eq!(manager.get_command(), ["-c", "a.c", "-std=c17"]);
+        assert_eq!(
+            diff.modified,
+            [(String::from("-std=c11"), String::from("-std=c17"))]
+        );
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(
+            manager.options[2].provenance.origin,
+            Some(OptionOrigin::Argument(1))
+        );
+    }
+
+    #[test]
+    fn a_replaced_option_is_removed_and_added() {
+        let (_, diff) = visit(&["-std=c11", "-c", "a.c"], |options| {
+            options[0] = CompilerOption::from_arg("-std=c17");
+        });
+        assert_eq!(diff.removed, ["-std=c11"]);
+        assert_eq!(diff.added, ["-std=c17"]);
+    }
+}
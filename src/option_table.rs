@@ -0,0 +1,389 @@
+use crate::compiler_option::CompilerOption;
+
+/// How an option takes its argument, modeled on the option classes used by
+/// clang's `Options.td`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    /// A plain switch without argument, e.g. `-c`.
+    Flag,
+    /// The argument is glued to the spelling, e.g. `-Ifoo` or `-std=c11`.
+    Joined,
+    /// The argument is the next command line element, e.g. `-Xclang -load`.
+    Separate,
+    /// Either `-Ifoo` or `-I foo`. A parsed option is always resolved to
+    /// `Joined` or `Separate`, so the original form is kept.
+    JoinedOrSeparate,
+    /// Two arguments, one glued to the spelling and the next command line
+    /// element, e.g. `-Xarch_arm64 -O2`.
+    JoinedAndSeparate,
+    /// The argument is a comma separated list, e.g. `-Wl,-z,defs`.
+    CommaJoined,
+    /// A positional argument (source file, object, archive, ...).
+    Input,
+}
+
+/// How the argument of an option is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueGrammar {
+    /// One opaque value, never split (e.g. `-DLIST=a,b`).
+    Single,
+    /// An ordered, comma separated list forwarded to another tool (e.g. `-Wl,-rpath,/x`).
+    Sequence,
+    /// An unordered, comma separated set whose values can be merged (e.g. `-fsanitize=`).
+    Set,
+}
+
+pub struct OptionSpec {
+    pub spelling: &'static str,
+    pub kind: OptionKind,
+    pub grammar: ValueGrammar,
+}
+
+macro_rules! define_option_table {
+    ($($spelling:expr => $kind:ident $(($grammar:ident))?),* $(,)?) => {
+        pub const OPTION_TABLE: &[OptionSpec] = &[
+            $(
+                OptionSpec {
+                    spelling: $spelling,
+                    kind: OptionKind::$kind,
+                    grammar: define_option_table!(@grammar $($grammar)?),
+                },
+            )*
+        ];
+    };
+    (@grammar) => { ValueGrammar::Single };
+    (@grammar $grammar:ident) => { ValueGrammar::$grammar };
+}
+
+// Options that take an argument, or whose spelling is a prefix of another one.
+// Unknown options are flags, or single valued joined options if they contain `=`.
+define_option_table! {
+    // output and language
+    "-o" => JoinedOrSeparate,
+    "-x" => JoinedOrSeparate,
+
+    // preprocessor
+    "-D" => JoinedOrSeparate,
+    "-U" => JoinedOrSeparate,
+    "-I" => JoinedOrSeparate,
+    "-F" => JoinedOrSeparate,
+    "-include" => JoinedOrSeparate,
+    "-include-pch" => Separate,
+    "-imacros" => JoinedOrSeparate,
+    "-idirafter" => JoinedOrSeparate,
+    "-iprefix" => JoinedOrSeparate,
+    "-iquote" => JoinedOrSeparate,
+    "-isysroot" => JoinedOrSeparate,
+    "-isystem" => JoinedOrSeparate,
+    "-iwithprefix" => JoinedOrSeparate,
+    "-iwithprefixbefore" => JoinedOrSeparate,
+    "-iframework" => JoinedOrSeparate,
+    "-cxx-isystem" => JoinedOrSeparate,
+    "-ivfsoverlay" => JoinedOrSeparate,
+
+    // dependency files
+    "-MF" => JoinedOrSeparate,
+    "-MJ" => JoinedOrSeparate,
+    "-MQ" => JoinedOrSeparate,
+    "-MT" => JoinedOrSeparate,
+
+    // linker
+    "-L" => JoinedOrSeparate,
+    "-l" => JoinedOrSeparate,
+    "-z" => Separate,
+    "-B" => JoinedOrSeparate,
+    "-T" => JoinedOrSeparate,
+    "-Tbss" => JoinedOrSeparate,
+    "-Tdata" => JoinedOrSeparate,
+    "-Ttext" => JoinedOrSeparate,
+    "-u" => JoinedOrSeparate,
+    "-undef" => Flag,
+    "-unwindlib=" => Joined,
+    // only separate, `-e<entry>` would also match `-emit-llvm` and friends
+    "-e" => Separate,
+    "-framework" => Separate,
+
+    // forwarding to other tools
+    "-Wa," => CommaJoined(Sequence),
+    "-Wl," => CommaJoined(Sequence),
+    "-Wp," => CommaJoined(Sequence),
+    "-Xassembler" => Separate,
+    "-Xclang" => Separate,
+    "-Xlinker" => Separate,
+    "-Xpreprocessor" => Separate,
+    "-Xarch_" => JoinedAndSeparate,
+    "-mllvm" => Separate,
+
+    // target and toolchain
+    "-arch" => Separate,
+    "-target" => Separate,
+    "--target=" => Joined,
+    "--sysroot" => Separate,
+    "--sysroot=" => Joined,
+    "--gcc-toolchain=" => Joined,
+    "-resource-dir" => Separate,
+    "--param" => Separate,
+
+    // language standard
+    "-std=" => Joined,
+    "-stdlib=" => Joined,
+
+    // sanitizers
+    "-fsanitize=" => CommaJoined(Set),
+    "-fno-sanitize=" => CommaJoined(Set),
+    "-fsanitize-recover=" => CommaJoined(Set),
+    "-fno-sanitize-recover=" => CommaJoined(Set),
+    "-fsanitize-trap=" => CommaJoined(Set),
+    "-fno-sanitize-trap=" => CommaJoined(Set),
+    "-fsanitize-coverage=" => CommaJoined(Set),
+    "-fno-sanitize-coverage=" => CommaJoined(Set),
+    "-fsanitize-memory-track-origins=" => Joined,
+
+    "-fsanitize-blacklist=" => Joined,
+    "-fsanitize-ignorelist=" => Joined,
+    "-fsanitize-coverage-allowlist=" => Joined,
+    "-fsanitize-coverage-ignorelist=" => Joined,
+    "-fsanitize-coverage-whitelist=" => Joined,
+    "-fsanitize-coverage-blacklist=" => Joined,
+
+    // plugins
+    "-fpass-plugin=" => Joined,
+}
+
+/// Find the table entry for an argument. Flags and separate options must match
+/// exactly, the others by prefix. The longest matching spelling wins.
+pub fn lookup(arg: &str) -> Option<&'static OptionSpec> {
+    OPTION_TABLE
+        .iter()
+        .filter(|spec| match spec.kind {
+            OptionKind::Flag | OptionKind::Separate | OptionKind::Input => arg == spec.spelling,
+            _ => arg.starts_with(spec.spelling),
+        })
+        .max_by_key(|spec| spec.spelling.len())
+}
+
+/// Tokenize a command line into options. Each logical option (e.g. `-o out`)
+/// becomes exactly one `CompilerOption` carrying its argument.
+pub fn parse_args<S: AsRef<str>>(args: &[S]) -> Vec<CompilerOption> {
+    let mut options = Vec::new();
+    let mut args = args.iter().map(|arg| arg.as_ref());
+    let mut only_inputs = false;
+
+    while let Some(arg) = args.next() {
+        if only_inputs || arg == "-" || !arg.starts_with('-') {
+            options.push(CompilerOption::new_input(arg));
+            continue;
+        }
+        if arg == "--" {
+            only_inputs = true;
+            options.push(CompilerOption::new(arg));
+            continue;
+        }
+
+        let option = match lookup(arg) {
+            Some(spec) => {
+                let joined_value = &arg[spec.spelling.len()..];
+                match spec.kind {
+                    OptionKind::Flag | OptionKind::Input => CompilerOption::new(arg),
+                    OptionKind::Joined => {
+                        CompilerOption::with_kind(spec.spelling, vec![joined_value], spec.kind)
+                    }
+                    OptionKind::CommaJoined => CompilerOption::with_kind(
+                        spec.spelling,
+                        joined_value.split(',').collect(),
+                        spec.kind,
+                    ),
+                    OptionKind::JoinedOrSeparate if !joined_value.is_empty() => {
+                        CompilerOption::with_kind(
+                            spec.spelling,
+                            vec![joined_value],
+                            OptionKind::Joined,
+                        )
+                    }
+                    OptionKind::JoinedAndSeparate => CompilerOption::with_kind(
+                        spec.spelling,
+                        std::iter::once(joined_value).chain(args.next()).collect(),
+                        spec.kind,
+                    ),
+                    OptionKind::Separate | OptionKind::JoinedOrSeparate => {
+                        CompilerOption::with_kind(
+                            spec.spelling,
+                            args.next().into_iter().collect(),
+                            OptionKind::Separate,
+                        )
+                    }
+                }
+            }
+            None => parse_unknown(arg),
+        };
+        options.push(option);
+    }
+
+    options
+}
+
+/// Options missing from the table: `-name=value` keeps its value as is,
+/// everything else is a flag.
+fn parse_unknown(arg: &str) -> CompilerOption {
+    match arg.split_once('=') {
+        Some((name, value)) => {
+            CompilerOption::with_kind(&format!("{}=", name), vec![value], OptionKind::Joined)
+        }
+        None => CompilerOption::new(arg),
+    }
+}
+
+/// The value grammar of a spelling. Options missing from the table take a single value.
+pub fn grammar_of_spelling(spelling: &str) -> ValueGrammar {
+    OPTION_TABLE
+        .iter()
+        .find(|spec| spec.spelling == spelling)
+        .map_or(ValueGrammar::Single, |spec| spec.grammar)
+}
+
+/// The kind used when a visitor creates an option from a spelling and values.
+pub fn kind_of_spelling(spelling: &str) -> OptionKind {
+    match OPTION_TABLE.iter().find(|spec| spec.spelling == spelling) {
+        Some(spec) => match spec.kind {
+            OptionKind::JoinedOrSeparate => OptionKind::Separate,
+            kind => kind,
+        },
+        None => OptionKind::Joined,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_of(options: &[CompilerOption]) -> Vec<Vec<String>> {
+        options.iter().map(|option| option.to_args()).collect()
+    }
+
+    fn inputs_of(options: &[CompilerOption]) -> Vec<&str> {
+        options
+            .iter()
+            .filter(|option| option.is_input())
+            .map(|option| option.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn separate_arguments_are_not_inputs() {
+        let options = parse_args(&[
+            "-u",
+            "sym",
+            "-T",
+            "link.ld",
+            "-e",
+            "start",
+            "-framework",
+            "Foundation",
+            "-iframework",
+            "/fw",
+            "-cxx-isystem",
+            "/inc",
+            "-ivfsoverlay",
+            "vfs.yaml",
+            "main.o",
+        ]);
+        assert_eq!(inputs_of(&options), ["main.o"]);
+        assert_eq!(options[0].name, "-u");
+        assert_eq!(options[0].values, ["sym"]);
+        assert_eq!(options[0].kind, OptionKind::Separate);
+        assert_eq!(options[2].name, "-e");
+        assert_eq!(options[2].values, ["start"]);
+    }
+
+    #[test]
+    fn joined_and_separate_forms() {
+        let options = parse_args(&["-usym", "-Tlink.ld", "-iframework/fw", "-cxx-isystem/inc"]);
+        assert_eq!(
+            args_of(&options),
+            [
+                ["-usym"],
+                ["-Tlink.ld"],
+                ["-iframework/fw"],
+                ["-cxx-isystem/inc"]
+            ]
+        );
+        assert!(options
+            .iter()
+            .all(|option| option.kind == OptionKind::Joined));
+        assert_eq!(options[0].name, "-u");
+        assert_eq!(options[0].values, ["sym"]);
+    }
+
+    #[test]
+    fn longer_spellings_win_over_prefixes() {
+        let options = parse_args(&["-undef", "-unwindlib=libgcc", "-emit-llvm", "-Ttext", "0x0"]);
+        assert_eq!(options[0].name, "-undef");
+        assert_eq!(options[0].kind, OptionKind::Flag);
+        assert_eq!(options[1].name, "-unwindlib=");
+        assert_eq!(options[2].name, "-emit-llvm");
+        assert_eq!(options[2].kind, OptionKind::Flag);
+        assert_eq!(options[3].name, "-Ttext");
+        assert_eq!(options[3].values, ["0x0"]);
+        assert!(inputs_of(&options).is_empty());
+    }
+
+    #[test]
+    fn xarch_takes_a_joined_and_a_separate_argument() {
+        let options = parse_args(&["-Xarch_arm64", "-O2", "a.c"]);
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[0].name, "-Xarch_");
+        assert_eq!(options[0].values, ["arm64", "-O2"]);
+        assert_eq!(
+            args_of(&options),
+            [vec!["-Xarch_arm64", "-O2"], vec!["a.c"]]
+        );
+        assert_eq!(options[0].to_string(), "-Xarch_arm64 -O2");
+    }
+
+    #[test]
+    fn everything_after_double_dash_is_an_input() {
+        let options = parse_args(&["-c", "--", "-u", "-o", "a.c"]);
+        assert_eq!(inputs_of(&options), ["-u", "-o", "a.c"]);
+        assert_eq!(options[1].name, "--");
+        assert!(!options[1].is_input());
+    }
+
+    #[test]
+    fn values_follow_the_grammar_of_the_option() {
+        let options = parse_args(&[
+            "-DLIST=a,b",
+            "-Wl,-rpath,/x",
+            "-fsanitize=address,undefined",
+            "-fsome-unknown=a,b",
+        ]);
+        assert_eq!(options[0].values, ["LIST=a,b"]);
+        assert_eq!(options[1].values, ["-rpath", "/x"]);
+        assert_eq!(options[2].values, ["address", "undefined"]);
+        assert_eq!(options[3].name, "-fsome-unknown=");
+        assert_eq!(options[3].values, ["a,b"]);
+        assert_eq!(
+            args_of(&options),
+            [
+                ["-DLIST=a,b"],
+                ["-Wl,-rpath,/x"],
+                ["-fsanitize=address,undefined"],
+                ["-fsome-unknown=a,b"]
+            ]
+        );
+    }
+
+    #[test]
+    fn only_sets_are_lists() {
+        assert_eq!(grammar_of_spelling("-Wl,"), ValueGrammar::Sequence);
+        assert_eq!(grammar_of_spelling("-fsanitize="), ValueGrammar::Set);
+        assert_eq!(grammar_of_spelling("-fsome-unknown="), ValueGrammar::Single);
+        let options = parse_args(&["-fsanitize=address", "-Wl,--as-needed", "-DA=1"]);
+        assert_eq!(
+            options
+                .iter()
+                .map(|option| option.is_list())
+                .collect::<Vec<_>>(),
+            [true, false, false]
+        );
+    }
+}
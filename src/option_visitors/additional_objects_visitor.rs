@@ -1,6 +1,12 @@
+use std::path::Path;
+
 use crate::{
-    compiler_option::{CompilerOption, OptionManagement},
+    compile_mode::CompileMode,
+    compiler_option::{CompilerOption, InsertPosition, OptionManagement},
+    context::VisitorContext,
     env::ADD_ADDITIONAL_OBJECTS,
+    error::ArgusError,
+    object::{validate_object, ObjectProblem},
 };
 
 use super::OptionVisitor;
@@ -9,11 +15,41 @@ use super::OptionVisitor;
 pub struct AdditionalObjectsVisitor {}
 
 impl OptionVisitor for AdditionalObjectsVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        let object_list =
-            std::env::var(ADD_ADDITIONAL_OBJECTS).unwrap_or_else(|_| String::from(""));
-        for object in object_list.split(',') {
-            options.add_or_modify(&CompilerOption::new(object));
+    fn name(&self) -> &'static str {
+        "AdditionalObjectsVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        if !matches!(
+            context.mode,
+            CompileMode::LinkExecutable | CompileMode::LinkShared
+        ) {
+            return Ok(());
+        }
+
+        let object_list = context.config.get(ADD_ADDITIONAL_OBJECTS).unwrap_or("");
+        for object in object_list.split(',').filter(|object| !object.is_empty()) {
+            // a missing file is reported by the linker already, and inputs
+            // that are not ELF (e.g. linker scripts like glibc's libc.so) are
+            // left to it too
+            if Path::new(object).exists() {
+                match validate_object(Path::new(object), options, &[]) {
+                    Err(ArgusError::InvalidObject {
+                        problem: ObjectProblem::NotElf,
+                        ..
+                    }) => {}
+                    result => result?,
+                }
+            }
+            let object = CompilerOption::new_input(object);
+            if !options.contains_option(&object) {
+                options.insert_option(&object, InsertPosition::InsideLinkerGroup);
+            }
         }
+        Ok(())
     }
 }
@@ -1,54 +1,37 @@
 use crate::{
     compiler_option::{CompilerOption, OptionManagement},
+    context::VisitorContext,
     env::ADD_ADDITIONAL_PASSES,
-    llvm, object,
+    error::ArgusError,
 };
 
 use super::OptionVisitor;
 
 /// Add LLVM pass
-pub struct LLVMPassManager {
-    llvm_version: u32,
+pub struct LLVMPassManager<'a> {
+    context: &'a VisitorContext,
 }
 
-impl LLVMPassManager {
-    pub fn new() -> Self {
-        let llvm_version = llvm::get_llvm_major_version().expect("Could not get LLVM version");
-        LLVMPassManager { llvm_version }
+impl<'a> LLVMPassManager<'a> {
+    pub fn new(context: &'a VisitorContext) -> Self {
+        LLVMPassManager { context }
     }
 
-    pub fn add_llvm_pass(&self, options: &mut Vec<CompilerOption>, pass: &str) {
-        let pass_object = object::find_object(pass)
-            .unwrap_or_else(|| panic!("Could not find pass object file {}", pass));
-        let pass_path = pass_object
-            .canonicalize()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
-        match self.llvm_version {
-            1..=10 => {
-                options.add_option(&CompilerOption::new("-Xclang"));
-                options.add_option(&CompilerOption::new("-load"));
-                options.add_option(&CompilerOption::new("-Xclang"));
-                options.add_option(&CompilerOption::new(&pass_path));
-            }
-            11..=15 => {
-                options.add_option(&CompilerOption::new("-fexperimental-new-pass-manager"));
-                options.add_option(&CompilerOption::new_with_value(
-                    "-fpass-plugin",
-                    vec![&pass_path],
-                ));
-            }
-            16..=18 => {
-                options.add_option(&CompilerOption::new_with_value(
-                    "-fpass-plugin",
-                    vec![&pass_path],
-                ));
-            }
-            _ => {
-                panic!("Unsupported LLVM version");
-            }
+    pub fn add_llvm_pass(
+        &self,
+        options: &mut Vec<CompilerOption>,
+        pass: &str,
+    ) -> Result<(), ArgusError> {
+        let pass_path = self.context.locate_object("pass plugin", pass)?;
+        // the plugin is loaded into the pass manager the command runs
+        let plugin_options = self
+            .context
+            .llvm_compat()
+            .pass_plugin_options(&pass_path, options);
+        for option in plugin_options {
+            options.add_option(&option);
         }
+        Ok(())
     }
 }
 
@@ -63,14 +46,25 @@ impl AdditionalPassesVisitor {
 }
 
 impl OptionVisitor for AdditionalPassesVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        let pass_manager = LLVMPassManager::new();
+    fn name(&self) -> &'static str {
+        "AdditionalPassesVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        let pass_manager = LLVMPassManager::new(context);
 
         // read pass list from the environment variable
-        let pass_list =
-            std::env::var(ADD_ADDITIONAL_PASSES).unwrap_or_else(|_| String::from("SanCovPass.so"));
+        let pass_list = context
+            .config
+            .get(ADD_ADDITIONAL_PASSES)
+            .unwrap_or("SanCovPass.so");
         for pass in pass_list.split(',') {
-            pass_manager.add_llvm_pass(options, pass);
+            pass_manager.add_llvm_pass(options, pass)?;
         }
+        Ok(())
     }
 }
@@ -1,6 +1,8 @@
 use crate::{
     compiler_option::{CompilerOption, OptionManagement},
+    context::VisitorContext,
     env::OPT_LEVEL,
+    error::ArgusError,
 };
 
 use super::OptionVisitor;
@@ -22,7 +24,7 @@ impl DefaultOptimizationVisitor {
         }
     }
 
-    pub fn init(&mut self, options: &Vec<CompilerOption>) {
+    pub fn init(&mut self, context: &VisitorContext, options: &Vec<CompilerOption>) {
         let mut option_level = None;
         if !options.get_options("-O3").is_empty() {
             option_level = Some(3);
@@ -35,7 +37,7 @@ impl DefaultOptimizationVisitor {
         }
 
         // Override by environment variable
-        if let Ok(level) = std::env::var(OPT_LEVEL) {
+        if let Some(level) = context.config.get(OPT_LEVEL) {
             if let Ok(level) = level.parse::<u8>() {
                 if (0..=3).contains(&level) {
                     option_level = Some(level);
@@ -46,8 +48,11 @@ impl DefaultOptimizationVisitor {
     }
 }
 
-fn disable_optimization_options(options: &mut Vec<CompilerOption>) {
-    options.retain(|opt| !opt.name.starts_with("-O"));
+fn disable_optimization_options(options: &mut [CompilerOption]) {
+    options
+        .iter_mut()
+        .filter(|opt| opt.name.starts_with("-O"))
+        .for_each(|opt| opt.disable());
 }
 
 fn enable_o0(options: &mut Vec<CompilerOption>) {
@@ -68,8 +73,16 @@ fn enable_o3(options: &mut Vec<CompilerOption>) {
 }
 
 impl OptionVisitor for DefaultOptimizationVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        self.init(options);
+    fn name(&self) -> &'static str {
+        "DefaultOptimizationVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        self.init(context, options);
         disable_optimization_options(options);
         match self.optimization_level {
             Some(0) => enable_o0(options),
@@ -78,5 +91,6 @@ impl OptionVisitor for DefaultOptimizationVisitor {
             Some(3) => enable_o3(options),
             _ => enable_o0(options), // by default enable O0
         }
+        Ok(())
     }
 }
@@ -1,7 +1,8 @@
 use super::OptionVisitor;
 use crate::{
     compiler_option::{CompilerOption, OptionManagement},
-    llvm,
+    context::VisitorContext,
+    error::ArgusError,
 };
 
 pub struct DefaultParametersVisitor;
@@ -18,8 +19,8 @@ impl DefaultParametersVisitor {
     }
 }
 
-fn add_llvm_lib(options: &mut Vec<CompilerOption>) {
-    let llvm_dir = llvm::get_llvm_libdir().expect("Could not find LLVM lib directory");
+fn add_llvm_lib(context: &VisitorContext, options: &mut Vec<CompilerOption>) {
+    let llvm_dir = &context.toolchain.libdir;
     // check if llvm_dir is in /usr or /lib
     if !llvm_dir.starts_with("/usr") && !llvm_dir.starts_with("/lib") {
         options.push(CompilerOption::from_arg(&format!(
@@ -29,31 +30,51 @@ fn add_llvm_lib(options: &mut Vec<CompilerOption>) {
     }
 }
 
-fn disable_unfriendly_options(options: &mut Vec<CompilerOption>) {
-    let unfriendly_options = vec![
-        "-Wl,-z,defs",
-        "-Wl,--no-undefined",
-        "-Wl,-no-undefined",
-        "--no-undefined",
-    ];
+fn disable_unfriendly_options(options: &mut Vec<CompilerOption>) -> Result<(), ArgusError> {
+    let unfriendly_linker_options = vec!["--no-undefined", "-no-undefined"];
 
-    for option in unfriendly_options {
-        for option in options.get_mut_options(option) {
-            option.disable();
+    for option in options.get_mut_options("--no-undefined") {
+        option.disable();
+    }
+    for linker_option in ["-Wl,", "-Xlinker"] {
+        for option in options.get_mut_options(linker_option) {
+            for value in &unfriendly_linker_options {
+                if option.contains(value) {
+                    option.remove_value(value)?;
+                }
+            }
         }
     }
 
-    // remove the -z defs options. This requires some hacky code.
-    let mut i = 0;
-    while i < options.len() {
-        if (options[i].name == "-z" || options[i].name == "-Wl,-z") && (i + 1 < options.len()) {
-            // check the next option
-            if options[i + 1].name == "defs" || options[i + 1].name == "-Wl,defs" {
-                options[i].disable();
-                options[i + 1].disable();
-                i += 1;
+    // remove the -z defs options, in any of these forms:
+    // `-z defs`, `-Wl,-z,defs`, `-Wl,-z -Wl,defs` and `-Xlinker -z -Xlinker defs`.
+    for option in options.get_mut_options("-z") {
+        if option.contains("defs") {
+            option.disable();
+        }
+    }
+    for option in options.get_mut_options("-Wl,") {
+        if let Some(i) = option.values.windows(2).position(|w| w == ["-z", "defs"]) {
+            option.values.drain(i..i + 2);
+            if option.values.is_empty() {
+                option.disable();
             }
         }
+    }
+    let mut i = 0;
+    while i + 1 < options.len() {
+        let (current, next) = (&options[i], &options[i + 1]);
+        if current.is_enabled
+            && next.is_enabled
+            && current.name == next.name
+            && (current.name == "-Wl," || current.name == "-Xlinker")
+            && current.values == ["-z"]
+            && next.values == ["defs"]
+        {
+            options[i].disable();
+            options[i + 1].disable();
+            i += 1;
+        }
         i += 1;
     }
 
@@ -66,14 +87,24 @@ fn disable_unfriendly_options(options: &mut Vec<CompilerOption>) {
             }
         });
     }
+    Ok(())
 }
 
 impl OptionVisitor for DefaultParametersVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        disable_unfriendly_options(options);
+    fn name(&self) -> &'static str {
+        "DefaultParametersVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        disable_unfriendly_options(options)?;
         options.add_or_modify(&CompilerOption::new("-Wno-unused-command-line-argument"));
-        add_llvm_lib(options);
+        add_llvm_lib(context, options);
         options.add_or_modify(&CompilerOption::new("-g"));
         options.add_or_modify(&CompilerOption::new("-fPIC"));
+        Ok(())
     }
 }
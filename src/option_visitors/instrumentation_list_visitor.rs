@@ -0,0 +1,93 @@
+use crate::{
+    compiler_option::{CompilerOption, OptionManagement},
+    context::VisitorContext,
+    env::{COVERAGE_ALLOWLIST, COVERAGE_DENYLIST, SANITIZER_IGNORELIST},
+    error::ArgusError,
+    input_file::InputKind,
+    special_case_list::{write_list, SpecialCaseList},
+};
+
+use super::OptionVisitor;
+
+/// `(environment variable, flag, whether the list is an allowlist)`.
+const LISTS: &[(&str, &str, bool)] = &[
+    (COVERAGE_ALLOWLIST, "-fsanitize-coverage-allowlist=", true),
+    (COVERAGE_DENYLIST, "-fsanitize-coverage-ignorelist=", false),
+    (SANITIZER_IGNORELIST, "-fsanitize-ignorelist=", false),
+];
+
+/// Visitor to restrict coverage and sanitizer instrumentation to some source
+/// files and functions, like `AFL_LLVM_ALLOWLIST` and `AFL_LLVM_DENYLIST`.
+/// The lists are generated from the entries of the environment variables and
+/// added to the compile steps they apply to.
+#[derive(Default)]
+pub struct InstrumentationListVisitor {}
+
+impl OptionVisitor for InstrumentationListVisitor {
+    fn name(&self) -> &'static str {
+        "InstrumentationListVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        let sources: Vec<String> = options
+            .inputs()
+            .into_iter()
+            .filter(|input| {
+                matches!(
+                    input.kind,
+                    InputKind::C | InputKind::Cxx | InputKind::ObjC | InputKind::ObjCxx
+                )
+            })
+            .map(|input| input.path)
+            .collect();
+        if sources.is_empty() {
+            return Ok(());
+        }
+
+        for &(env, flag, allowlist) in LISTS {
+            let Some(spec) = context.config.get(env) else {
+                continue;
+            };
+            let list = SpecialCaseList::parse(spec);
+            if list == SpecialCaseList::default() || !is_instrumented(options, flag) {
+                continue;
+            }
+
+            let content = if allowlist {
+                // sources outside the allowlist must get it too, or they
+                // would be instrumented entirely
+                list.to_allowlist()
+            } else if !list.functions.is_empty()
+                || sources.iter().any(|source| list.matches_source(source))
+            {
+                list.to_ignorelist()
+            } else {
+                continue;
+            };
+            let path = write_list(&content)?;
+            let option = CompilerOption::new_with_value(flag, vec![&path.to_string_lossy()]);
+            if !options.contains_option(&option) {
+                options.push(option);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the command has the instrumentation the list flag applies to:
+/// SanitizerCoverage (also implied by libFuzzer) or any sanitizer.
+fn is_instrumented(options: &Vec<CompilerOption>, flag: &str) -> bool {
+    let sanitizers = options.get_options("-fsanitize=");
+    if flag.starts_with("-fsanitize-coverage-") {
+        !options.get_options("-fsanitize-coverage=").is_empty()
+            || sanitizers.iter().any(|sanitizer_options| {
+                sanitizer_options.contains("fuzzer") || sanitizer_options.contains("fuzzer-no-link")
+            })
+    } else {
+        !sanitizers.is_empty()
+    }
+}
@@ -1,7 +1,13 @@
-use crate::compiler_option::{CompilerOption, OptionManagement};
+use std::path::Path;
+
+use crate::compile_mode::CompileMode;
+use crate::compiler_option::{CompilerOption, InsertPosition, OptionManagement};
+use crate::context::VisitorContext;
 use crate::env::DRIVER;
-use crate::object::find_object;
+use crate::error::ArgusError;
+use crate::object::validate_object;
 use crate::option_visitors::OptionVisitor;
+use crate::target::Arch;
 
 /// Visitor to remove the fuzzer sanitizer from the options. If libFuzzer is used, replace it with the "FUZZER_LIB".
 #[derive(Default)]
@@ -9,42 +15,59 @@ pub struct LibfuzzerVisitor;
 
 impl LibfuzzerVisitor {
     pub fn new() -> Self {
-        Self::default()
+        LibfuzzerVisitor
     }
 }
 
 impl OptionVisitor for LibfuzzerVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        if self.remove_fuzzer_sanitizer(options) {
-            self.add_driver_library(options);
+    fn name(&self) -> &'static str {
+        "LibfuzzerVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        if self.remove_fuzzer_sanitizer(options)? {
+            self.add_driver_library(context, options)?;
         }
+        Ok(())
     }
 }
 
 impl LibfuzzerVisitor {
     /// Removes the fuzzer sanitizer from the options if present.
     /// Returns true if the fuzzer sanitizer was removed, false otherwise.
-    fn remove_fuzzer_sanitizer(&self, options: &mut Vec<CompilerOption>) -> bool {
+    fn remove_fuzzer_sanitizer(
+        &self,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<bool, ArgusError> {
         let mut fuzzer_found = false;
 
-        for sanitizer_options in options.get_mut_options("-fsanitize") {
+        for sanitizer_options in options.get_mut_options("-fsanitize=") {
             if sanitizer_options.contains("fuzzer") {
-                sanitizer_options.remove_value("fuzzer");
+                sanitizer_options.remove_value("fuzzer")?;
                 fuzzer_found = true;
             }
         }
 
-        fuzzer_found
+        Ok(fuzzer_found)
     }
 
     /// Adds the appropriate driver library based on the environment and options.
-    fn add_driver_library(&self, options: &mut Vec<CompilerOption>) {
-        if options.is_compiling() || options.is_preprocessor() || options.is_checking() {
-            return;
+    fn add_driver_library(
+        &self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        // The driver defines `main`, so it only goes into executables.
+        if context.mode != CompileMode::LinkExecutable {
+            return Ok(());
         }
 
         let nonstd = options
-            .get_options("-stdlib")
+            .get_options("-stdlib=")
             .iter()
             .any(|stdlib_options| stdlib_options.contains("libc++"));
 
@@ -54,25 +77,22 @@ impl LibfuzzerVisitor {
             "bandfuzz-driver.o"
         };
 
-        let driver = std::env::var(DRIVER).unwrap_or_else(|_| default_driver.to_string());
+        let driver = context.config.get(DRIVER).unwrap_or(default_driver);
 
-        if let Some(driver_library) = find_object(&driver) {
-            options.add_or_modify(&CompilerOption::new(
-                driver_library
-                    .canonicalize()
-                    .unwrap()
-                    .to_string_lossy()
-                    .as_ref(),
-            ));
+        let arch = Arch::from_options(options);
+        let driver_path = context.locate_object_for("fuzzer driver", driver, &arch)?;
+        validate_object(Path::new(&driver_path), options, &["main"])?;
+        let driver = CompilerOption::new_input(&driver_path);
+        // The driver references `LLVMFuzzerTestOneInput`, which may live in
+        // a static archive, so it has to come before all inputs.
+        if !options.contains_option(&driver) {
+            options.insert_option(&driver, InsertPosition::BeforeFirstInput);
+        }
 
-            if !nonstd {
-                options.add_or_modify(&CompilerOption::new("-lstdc++"));
-            }
-        } else {
-            panic!(
-                "Could not find the driver library for the current FUZZER_LIB: {}",
-                driver
-            );
+        let stdcxx = CompilerOption::from_arg("-lstdc++");
+        if !nonstd && !options.contains_option(&stdcxx) {
+            options.insert_option(&stdcxx, InsertPosition::OutsideLinkerGroups);
         }
+        Ok(())
     }
 }
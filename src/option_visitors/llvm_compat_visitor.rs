@@ -0,0 +1,36 @@
+use crate::{compiler_option::CompilerOption, context::VisitorContext, error::ArgusError};
+
+use super::OptionVisitor;
+
+/// Adapt the final command to the LLVM release: renamed flags get the spelling
+/// the release understands, flags it rejects are dropped.
+#[derive(Default)]
+pub struct LlvmCompatVisitor {}
+
+impl LlvmCompatVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OptionVisitor for LlvmCompatVisitor {
+    fn name(&self) -> &'static str {
+        "LlvmCompatVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        let compat = context.llvm_compat();
+        for option in options.iter_mut().filter(|opt| opt.is_enabled) {
+            if compat.is_removed(&option.name) {
+                option.disable();
+            } else if let Some(spelling) = compat.renamed_spelling(&option.name) {
+                option.name = spelling.to_string();
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
-use crate::compiler_option::CompilerOption;
+use crate::{compiler_option::CompilerOption, context::VisitorContext, error::ArgusError};
 
 mod additional_objects_visitor;
 mod additional_passes_visitor;
 mod default_optimization_visitor;
 mod default_parameters_visitor;
+mod instrumentation_list_visitor;
 mod libfuzzer_visitor;
+mod llvm_compat_visitor;
 mod profile_visitor;
+mod registry;
 mod runtime_visitor;
+mod sanitizer_options_visitor;
 mod sanitizer_visitor;
 mod x_visitor;
 
@@ -14,12 +18,22 @@ pub use additional_objects_visitor::AdditionalObjectsVisitor;
 pub use additional_passes_visitor::AdditionalPassesVisitor;
 pub use default_optimization_visitor::DefaultOptimizationVisitor;
 pub use default_parameters_visitor::DefaultParametersVisitor;
+pub use instrumentation_list_visitor::InstrumentationListVisitor;
 pub use libfuzzer_visitor::LibfuzzerVisitor;
+pub use llvm_compat_visitor::LlvmCompatVisitor;
 pub use profile_visitor::ProfileVisitor;
+pub use registry::{VisitorEntry, VisitorPhase, VisitorRegistry};
 pub use runtime_visitor::RuntimeVisitor;
+pub use sanitizer_options_visitor::SanitizerOptionsVisitor;
 pub use sanitizer_visitor::SanitizerVisitor;
 pub use x_visitor::XVisitor;
 
 pub trait OptionVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>);
+    /// A stable name, used to attribute changes to the visitor.
+    fn name(&self) -> &'static str;
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError>;
 }
@@ -1,4 +1,8 @@
-use crate::compiler_option::{CompilerOption, OptionManagement};
+use crate::{
+    compiler_option::{CompilerOption, OptionManagement},
+    context::VisitorContext,
+    error::ArgusError,
+};
 
 use super::OptionVisitor;
 
@@ -12,8 +16,17 @@ impl ProfileVisitor {
 }
 
 impl OptionVisitor for ProfileVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
+    fn name(&self) -> &'static str {
+        "ProfileVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        _context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
         options.add_option(&CompilerOption::new("-fprofile-instr-generate"));
         options.add_option(&CompilerOption::new("-fcoverage-mapping"));
+        Ok(())
     }
 }
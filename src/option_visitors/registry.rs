@@ -0,0 +1,432 @@
+use crate::{
+    context::Config,
+    env::{
+        ADD_ADDITIONAL_OBJECTS, ADD_ADDITIONAL_PASSES, ADD_DRIVER, ADD_RUNTIME,
+        ARGUS_DISABLE_VISITORS, ARGUS_VISITORS, ASAN_DEFAULT_OPTIONS, COVERAGE_ALLOWLIST,
+        COVERAGE_DENYLIST, LSAN_DEFAULT_OPTIONS, MSAN_DEFAULT_OPTIONS, PROFILING,
+        SANITIZER_IGNORELIST, UBSAN_DEFAULT_OPTIONS,
+    },
+    error::ArgusError,
+};
+
+use super::{
+    AdditionalObjectsVisitor, AdditionalPassesVisitor, DefaultOptimizationVisitor,
+    DefaultParametersVisitor, InstrumentationListVisitor, LibfuzzerVisitor, LlvmCompatVisitor,
+    OptionVisitor, ProfileVisitor, RuntimeVisitor, SanitizerOptionsVisitor, SanitizerVisitor,
+    XVisitor,
+};
+
+/// When a visitor runs relative to the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VisitorPhase {
+    /// Ordered by the `before` and `after` constraints.
+    Main,
+    /// After every visitor of the main phase, registered ones included.
+    Final,
+}
+
+/// A visitor known to the registry.
+pub struct VisitorEntry {
+    /// Stable name, the same as `OptionVisitor::name`.
+    pub name: &'static str,
+    pub phase: VisitorPhase,
+    /// Visitors this one has to run before, if they are selected.
+    pub before: &'static [&'static str],
+    /// Visitors this one has to run after, if they are selected.
+    pub after: &'static [&'static str],
+    /// Whether the visitor runs when `ARGUS_VISITORS` is not set.
+    pub enabled_by_default: fn(&Config) -> bool,
+    pub create: fn() -> Box<dyn OptionVisitor>,
+}
+
+#[derive(Default)]
+pub struct VisitorRegistry {
+    entries: Vec<VisitorEntry>,
+}
+
+impl VisitorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The registry with all visitors shipped with Argus.
+    pub fn with_builtin_visitors() -> Self {
+        let entries = vec![
+            VisitorEntry {
+                name: "DefaultParametersVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &[],
+                enabled_by_default: |_| true,
+                create: || Box::<DefaultParametersVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "DefaultOptimizationVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["DefaultParametersVisitor"],
+                enabled_by_default: |_| true,
+                create: || Box::<DefaultOptimizationVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "SanitizerVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["DefaultOptimizationVisitor"],
+                enabled_by_default: |_| true,
+                create: || Box::<SanitizerVisitor>::default(),
+            },
+            // inputs added after this visitor are kept out of the user's -x language
+            VisitorEntry {
+                name: "XVisitor",
+                phase: VisitorPhase::Main,
+                before: &[
+                    "LibfuzzerVisitor",
+                    "RuntimeVisitor",
+                    "AdditionalObjectsVisitor",
+                    "SanitizerOptionsVisitor",
+                    "InstrumentationListVisitor",
+                ],
+                after: &["SanitizerVisitor"],
+                enabled_by_default: |_| true,
+                create: || Box::<XVisitor>::default(),
+            },
+            // sees `fuzzer` before LibfuzzerVisitor removes it
+            VisitorEntry {
+                name: "InstrumentationListVisitor",
+                phase: VisitorPhase::Main,
+                before: &["LibfuzzerVisitor"],
+                after: &["SanitizerVisitor"],
+                enabled_by_default: |config| {
+                    [COVERAGE_ALLOWLIST, COVERAGE_DENYLIST, SANITIZER_IGNORELIST]
+                        .iter()
+                        .any(|env| config.is_set(env))
+                },
+                create: || Box::<InstrumentationListVisitor>::default(),
+            },
+            // removes `fuzzer` from the final list of sanitizers
+            VisitorEntry {
+                name: "LibfuzzerVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["SanitizerVisitor"],
+                enabled_by_default: |config| config.is_set(ADD_DRIVER),
+                create: || Box::<LibfuzzerVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "RuntimeVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["LibfuzzerVisitor"],
+                enabled_by_default: |config| config.is_set(ADD_RUNTIME),
+                create: || Box::<RuntimeVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "ProfileVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["RuntimeVisitor"],
+                enabled_by_default: |config| config.is_set(PROFILING),
+                create: || Box::<ProfileVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "AdditionalPassesVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["RuntimeVisitor"],
+                enabled_by_default: |config| config.is_set(ADD_ADDITIONAL_PASSES),
+                create: || Box::<AdditionalPassesVisitor>::default(),
+            },
+            VisitorEntry {
+                name: "AdditionalObjectsVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["RuntimeVisitor"],
+                enabled_by_default: |config| config.is_set(ADD_ADDITIONAL_OBJECTS),
+                create: || Box::<AdditionalObjectsVisitor>::default(),
+            },
+            // adds an input, so it runs after XVisitor
+            VisitorEntry {
+                name: "SanitizerOptionsVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &["XVisitor"],
+                enabled_by_default: |config| {
+                    [
+                        ASAN_DEFAULT_OPTIONS,
+                        UBSAN_DEFAULT_OPTIONS,
+                        MSAN_DEFAULT_OPTIONS,
+                        LSAN_DEFAULT_OPTIONS,
+                    ]
+                    .iter()
+                    .any(|env| config.is_set(env))
+                },
+                create: || Box::<SanitizerOptionsVisitor>::default(),
+            },
+            // sees the options of all other visitors, registered ones included
+            VisitorEntry {
+                name: "LlvmCompatVisitor",
+                phase: VisitorPhase::Final,
+                before: &[],
+                after: &[],
+                enabled_by_default: |_| true,
+                create: || Box::<LlvmCompatVisitor>::default(),
+            },
+        ];
+        VisitorRegistry { entries }
+    }
+
+    pub fn register(&mut self, entry: VisitorEntry) -> Result<(), ArgusError> {
+        if self.entries.iter().any(|e| e.name == entry.name) {
+            return Err(ArgusError::DuplicateVisitor(entry.name));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Select the visitors from `ARGUS_VISITORS` (or the default ones), drop the
+    /// ones in `ARGUS_DISABLE_VISITORS` and create them in dependency order.
+    pub fn select(&self, config: &Config) -> Result<Vec<Box<dyn OptionVisitor>>, ArgusError> {
+        let mut selected: Vec<bool> = match config.get(ARGUS_VISITORS) {
+            Some(list) => {
+                let mut selected = vec![false; self.entries.len()];
+                for name in split_list(list) {
+                    selected[self.index_of(name)?] = true;
+                }
+                selected
+            }
+            None => self
+                .entries
+                .iter()
+                .map(|entry| (entry.enabled_by_default)(config))
+                .collect(),
+        };
+        if let Some(list) = config.get(ARGUS_DISABLE_VISITORS) {
+            for name in split_list(list) {
+                selected[self.index_of(name)?] = false;
+            }
+        }
+
+        Ok(self
+            .order(&selected)?
+            .into_iter()
+            .map(|index| (self.entries[index].create)())
+            .collect())
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, ArgusError> {
+        self.entries
+            .iter()
+            .position(|entry| entry.name == name)
+            .ok_or_else(|| ArgusError::UnknownVisitor(name.to_string()))
+    }
+
+    /// Topologically sort the selected visitors. Among the visitors that are
+    /// ready to run, the one registered first goes first.
+    fn order(&self, selected: &[bool]) -> Result<Vec<usize>, ArgusError> {
+        let count = self.entries.len();
+        // edges[a] contains b if a has to run before b
+        let mut edges = vec![Vec::new(); count];
+        let mut in_degree = vec![0; count];
+        let mut add_edge = |from: usize, to: usize| {
+            if selected[from] && selected[to] && !edges[from].contains(&to) {
+                edges[from].push(to);
+                in_degree[to] += 1;
+            }
+        };
+        for (index, entry) in self.entries.iter().enumerate() {
+            for (other, other_entry) in self.entries.iter().enumerate() {
+                if entry.phase < other_entry.phase {
+                    add_edge(index, other);
+                }
+            }
+            for &name in entry.before {
+                if let Ok(other) = self.index_of(name) {
+                    add_edge(index, other);
+                }
+            }
+            for &name in entry.after {
+                if let Ok(other) = self.index_of(name) {
+                    add_edge(other, index);
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut done = vec![false; count];
+        while let Some(next) =
+            (0..count).find(|&index| selected[index] && !done[index] && in_degree[index] == 0)
+        {
+            done[next] = true;
+            order.push(next);
+            for &to in &edges[next] {
+                in_degree[to] -= 1;
+            }
+        }
+
+        let remaining: Vec<&'static str> = (0..count)
+            .filter(|&index| selected[index] && !done[index])
+            .map(|index| self.entries[index].name)
+            .collect();
+        if !remaining.is_empty() {
+            return Err(ArgusError::VisitorCycle(remaining));
+        }
+        Ok(order)
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compiler_option::CompilerOption, context::VisitorContext};
+
+    #[derive(Default)]
+    struct CustomVisitor {}
+
+    impl OptionVisitor for CustomVisitor {
+        fn name(&self) -> &'static str {
+            "CustomVisitor"
+        }
+
+        fn visit(
+            &mut self,
+            _context: &VisitorContext,
+            _options: &mut Vec<CompilerOption>,
+        ) -> Result<(), ArgusError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn final_phase_runs_after_registered_visitors() {
+        let mut registry = VisitorRegistry::with_builtin_visitors();
+        registry
+            .register(VisitorEntry {
+                name: "CustomVisitor",
+                phase: VisitorPhase::Main,
+                before: &[],
+                after: &[],
+                enabled_by_default: |_| true,
+                create: || Box::<CustomVisitor>::default(),
+            })
+            .unwrap();
+        let names: Vec<&str> = registry
+            .select(&Config::default())
+            .unwrap()
+            .iter()
+            .map(|visitor| visitor.name())
+            .collect();
+        assert!(names.contains(&"CustomVisitor"));
+        assert_eq!(names.last(), Some(&"LlvmCompatVisitor"));
+    }
+
+    #[test]
+    fn llvm_compat_runs_after_every_builtin_visitor() {
+        let mut registry = VisitorRegistry::with_builtin_visitors();
+        // registration order breaks ties, so once LlvmCompatVisitor is
+        // registered first only the declared order puts it last
+        registry.entries.reverse();
+        let names: Vec<&str> = registry.entries.iter().map(|entry| entry.name).collect();
+        for name in names.iter().filter(|&&name| name != "LlvmCompatVisitor") {
+            let list = format!("LlvmCompatVisitor,{}", name);
+            let config = Config::from_vars([(ARGUS_VISITORS.to_string(), list)]);
+            let visitors = registry.select(&config).unwrap();
+            assert_eq!(
+                visitors.last().unwrap().name(),
+                "LlvmCompatVisitor",
+                "{}",
+                name
+            );
+        }
+    }
+
+    fn custom(
+        name: &'static str,
+        before: &'static [&'static str],
+        after: &'static [&'static str],
+    ) -> VisitorEntry {
+        VisitorEntry {
+            name,
+            phase: VisitorPhase::Main,
+            before,
+            after,
+            enabled_by_default: |_| true,
+            create: || Box::<CustomVisitor>::default(),
+        }
+    }
+
+    fn select(
+        registry: &VisitorRegistry,
+        vars: &[(&str, &str)],
+    ) -> Result<Vec<&'static str>, ArgusError> {
+        let config = Config::from_vars(
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        Ok(registry
+            .select(&config)?
+            .iter()
+            .map(|visitor| visitor.name())
+            .collect())
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let mut registry = VisitorRegistry::new();
+        registry.register(custom("A", &[], &["B"])).unwrap();
+        registry.register(custom("B", &[], &["C"])).unwrap();
+        registry.register(custom("C", &[], &["A"])).unwrap();
+        registry.register(custom("D", &[], &[])).unwrap();
+        match select(&registry, &[]) {
+            Err(ArgusError::VisitorCycle(names)) => assert_eq!(names, ["A", "B", "C"]),
+            other => panic!("expected a cycle, got {:?}", other.map(|names| names.len())),
+        }
+        // without one of its visitors there is no cycle
+        assert!(select(&registry, &[(ARGUS_DISABLE_VISITORS, "B")]).is_ok());
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        let registry = VisitorRegistry::with_builtin_visitors();
+        for name in [ARGUS_VISITORS, ARGUS_DISABLE_VISITORS] {
+            match select(&registry, &[(name, "XVisitor, NoSuchVisitor")]) {
+                Err(ArgusError::UnknownVisitor(visitor)) => assert_eq!(visitor, "NoSuchVisitor"),
+                other => panic!(
+                    "{}: expected an unknown visitor, got {:?}",
+                    name,
+                    other.ok()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn constraints_on_unselected_visitors_are_ignored() {
+        let registry = VisitorRegistry::with_builtin_visitors();
+        // XVisitor runs after SanitizerVisitor and before RuntimeVisitor
+        assert_eq!(
+            select(
+                &registry,
+                &[
+                    (ARGUS_VISITORS, "RuntimeVisitor,XVisitor,SanitizerVisitor"),
+                    (ARGUS_DISABLE_VISITORS, "XVisitor"),
+                ]
+            )
+            .unwrap(),
+            ["SanitizerVisitor", "RuntimeVisitor"]
+        );
+
+        // constraints naming a visitor that is not registered
+        let mut registry = VisitorRegistry::new();
+        registry
+            .register(custom("A", &["Missing"], &["Gone"]))
+            .unwrap();
+        assert_eq!(select(&registry, &[]).unwrap(), ["CustomVisitor"]);
+    }
+}
@@ -1,8 +1,14 @@
+use std::path::Path;
+
 use super::OptionVisitor;
 use crate::{
-    compiler_option::{CompilerOption, OptionManagement},
+    compile_mode::CompileMode,
+    compiler_option::{CompilerOption, InsertPosition, OptionManagement},
+    context::VisitorContext,
     env::RUNTIME,
-    object::find_object,
+    error::ArgusError,
+    object::validate_object,
+    target::Arch,
 };
 
 #[derive(Default)]
@@ -15,24 +21,57 @@ impl RuntimeVisitor {
 }
 
 impl OptionVisitor for RuntimeVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        if options.is_compiling() || options.is_preprocessor() || options.is_checking() {
-            return;
+    fn name(&self) -> &'static str {
+        "RuntimeVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        // The runtime belongs to the executable only. Shared libraries resolve
+        // its symbols from the executable that loads them.
+        if context.mode != CompileMode::LinkExecutable {
+            return Ok(());
+        }
+
+        let runtime_path = context.config.get(RUNTIME).unwrap_or("bandfuzz-rt.o");
+        let arch = Arch::from_options(options);
+        let runtime_path = context.locate_object_for("runtime object", runtime_path, &arch)?;
+        let runtime = CompilerOption::new_input(&runtime_path);
+        if options.contains_option(&runtime) {
+            return Ok(());
         }
 
-        let runtime_path = std::env::var(RUNTIME).unwrap_or_else(|_| "bandfuzz-rt.o".to_string());
-        let runtime = find_object(&runtime_path).unwrap_or_else(|| {
-            panic!(
-                "Could not find runtime object file {}",
-                runtime_path.as_str()
-            )
-        });
-
-        options.add_or_modify(&CompilerOption::new(
-            runtime.canonicalize().unwrap().to_string_lossy().as_ref(),
-        ));
-        options.add_or_mix(&CompilerOption::new("-lpthread"));
-        options.add_or_mix(&CompilerOption::new("-ldl"));
-        options.add_or_mix(&CompilerOption::new("-lgcc"));
+        // trace-pc-guard instrumentation calls into the runtime
+        let trace_pc_guard = options
+            .get_options("-fsanitize-coverage=")
+            .iter()
+            .any(|coverage| coverage.contains("trace-pc-guard"));
+        let provides: &[&'static str] = if trace_pc_guard {
+            &[
+                "__sanitizer_cov_trace_pc_guard",
+                "__sanitizer_cov_trace_pc_guard_init",
+            ]
+        } else {
+            &[]
+        };
+        validate_object(Path::new(&runtime_path), options, provides)?;
+
+        // Put the runtime next to the user objects (inside a linker group if
+        // there is one), so archives and libraries that follow can use it.
+        options.insert_option(&runtime, InsertPosition::InsideLinkerGroup);
+
+        // The libraries the runtime needs go right after it, even if the
+        // command links them already, so `--as-needed` keeps them. Inserted
+        // in reverse to end up as `-lpthread -ldl -lgcc`.
+        for library in ["-lgcc", "-ldl", "-lpthread"] {
+            options.insert_option(
+                &CompilerOption::from_arg(library),
+                InsertPosition::After(&runtime.name),
+            );
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,193 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    compile_mode::CompileMode,
+    compiler_option::{CompilerOption, InsertPosition, OptionManagement},
+    context::VisitorContext,
+    embedded::{fnv1a, write},
+    env::{
+        ARGUS_NO_CACHE, ASAN_DEFAULT_OPTIONS, LSAN_DEFAULT_OPTIONS, MSAN_DEFAULT_OPTIONS,
+        UBSAN_DEFAULT_OPTIONS,
+    },
+    error::ArgusError,
+    llvm::cache_dir,
+};
+
+use super::OptionVisitor;
+
+/// `(environment variable, function the sanitizer runtime calls for its
+/// default options)`.
+const DEFAULT_OPTIONS: &[(&str, &str)] = &[
+    (ASAN_DEFAULT_OPTIONS, "__asan_default_options"),
+    (UBSAN_DEFAULT_OPTIONS, "__ubsan_default_options"),
+    (MSAN_DEFAULT_OPTIONS, "__msan_default_options"),
+    (LSAN_DEFAULT_OPTIONS, "__lsan_default_options"),
+];
+
+/// Options that select the target, passed on when compiling the object.
+const TARGET_OPTIONS: &[&str] = &["-m32", "-m64", "-mx32", "-target", "--target="];
+
+/// Visitor to link an object defining the `__*san_default_options` functions
+/// into sanitized executables, so that they run with the options given at
+/// build time (e.g. `ARGUS_ASAN_DEFAULT_OPTIONS=abort_on_error=1:symbolize=0`)
+/// without `ASAN_OPTIONS` and friends. The environment still overrides them.
+#[derive(Default)]
+pub struct SanitizerOptionsVisitor {}
+
+impl OptionVisitor for SanitizerOptionsVisitor {
+    fn name(&self) -> &'static str {
+        "SanitizerOptionsVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        if context.mode != CompileMode::LinkExecutable
+            || options.get_options("-fsanitize=").is_empty()
+        {
+            return Ok(());
+        }
+
+        let defaults: Vec<(&str, &str)> = DEFAULT_OPTIONS
+            .iter()
+            .filter_map(|&(env, function)| context.config.get(env).map(|value| (function, value)))
+            .collect();
+        if defaults.is_empty() {
+            return Ok(());
+        }
+
+        let target_args: Vec<String> = options
+            .iter()
+            .filter(|opt| opt.is_enabled && TARGET_OPTIONS.contains(&opt.name.as_str()))
+            .flat_map(|opt| opt.to_args())
+            .collect();
+        let object = compile_defaults(context, &generate_source(&defaults), &target_args)?;
+        let object = CompilerOption::new_input(&object.to_string_lossy());
+        if !options.contains_option(&object) {
+            options.insert_option(&object, InsertPosition::AfterLastInput);
+        }
+        Ok(())
+    }
+}
+
+/// The C source defining the default options functions.
+fn generate_source(defaults: &[(&str, &str)]) -> String {
+    let mut source = String::from("/* Generated by Argus. */\n");
+    for (function, value) in defaults {
+        source.push_str(&format!(
+            "__attribute__((visibility(\"default\"), used))\nconst char *{}(void) {{\n  return \"{}\";\n}}\n",
+            function,
+            escape_c_string(value)
+        ));
+    }
+    source
+}
+
+fn escape_c_string(value: &str) -> String {
+    let mut escaped = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Compile the source with the clang of the toolchain, unless an object of
+/// the same source, compiler and target was compiled before. Objects are
+/// stored in `<cache dir>/sanitizer-options/<hash>.o`.
+fn compile_defaults(
+    context: &VisitorContext,
+    source: &str,
+    target_args: &[String],
+) -> Result<PathBuf, ArgusError> {
+    let clang = context.toolchain.clang()?;
+    let key = format!("{}\n{}\n{}", clang.display(), target_args.join(" "), source);
+    let name = format!("{:016x}", fnv1a(key.as_bytes()));
+    let dir = cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("argus"))
+        .join("sanitizer-options");
+    let object = dir.join(format!("{}.o", name));
+    if object.is_file() && !context.config.is_set(ARGUS_NO_CACHE) {
+        return Ok(object);
+    }
+
+    let source_name = format!("{}.c", name);
+    write(&dir, &source_name, source.as_bytes()).map_err(|source| ArgusError::Io {
+        path: dir.join(&source_name),
+        source,
+    })?;
+    // concurrent links may compile the same object, so compile to a private
+    // file and rename it into place
+    let temporary = dir.join(format!(".{}.o.{}", name, std::process::id()));
+    let mut command = Command::new(&clang);
+    command
+        .args(["-c", "-O1", "-fPIC", "-w"])
+        .args(target_args)
+        .arg(dir.join(&source_name))
+        .arg("-o")
+        .arg(&temporary);
+    let output = command.output().map_err(|source| ArgusError::Io {
+        path: clang.clone(),
+        source,
+    })?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&temporary);
+        return Err(ArgusError::CompileFailed {
+            what: "sanitizer default options",
+            command: format!("{:?}", command),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    rename(&temporary, &object)?;
+    Ok(object)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), ArgusError> {
+    fs::rename(from, to).map_err(|source| {
+        let _ = fs::remove_file(from);
+        ArgusError::Io {
+            path: to.to_path_buf(),
+            source,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_escaped_for_c() {
+        assert_eq!(
+            escape_c_string("abort_on_error=1:symbolize=0"),
+            "abort_on_error=1:symbolize=0"
+        );
+        assert_eq!(
+            escape_c_string("a\"b\\c\n\u{e9}"),
+            "a\\\"b\\\\c\\012\\303\\251"
+        );
+    }
+
+    #[test]
+    fn one_function_per_sanitizer() {
+        let source = generate_source(&[
+            ("__asan_default_options", "detect_leaks=0"),
+            ("__ubsan_default_options", "print_stacktrace=1"),
+        ]);
+        assert!(source.contains(
+            "const char *__asan_default_options(void) {\n  return \"detect_leaks=0\";\n}\n"
+        ));
+        assert!(source.contains("const char *__ubsan_default_options(void)"));
+        assert!(!source.contains("__msan_default_options"));
+    }
+}
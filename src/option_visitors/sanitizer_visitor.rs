@@ -1,15 +1,54 @@
+use std::{fs, path::Path};
+
 use super::OptionVisitor;
 use crate::{
+    compile_mode::CompileMode,
     compiler_option::{CompilerOption, OptionManagement},
-    env::{ENABLE_ASAN, ENABLE_COVSAN, ENABLE_MSAN, ENABLE_UBSAN, NOSANITIZER},
+    context::VisitorContext,
+    env::{
+        ARGUS_DEBUG, ARGUS_EXPLAIN, COVERAGE, ENABLE_ASAN, ENABLE_CFISAN, ENABLE_COVSAN,
+        ENABLE_HWASAN, ENABLE_KCFI, ENABLE_LSAN, ENABLE_MSAN, ENABLE_SAFESTACK, ENABLE_TSAN,
+        ENABLE_UBSAN, MSAN_LIBCXX, MSAN_ORIGINS, NOSANITIZER, UBSAN_CHECKS, UBSAN_MODE,
+    },
+    error::{report, ArgusError},
+    input_file::InputKind,
+    sanitizer::{
+        check_coverage, find_conflicts, loser, parse_coverage_spec, parse_ubsan_checks,
+        unsupported_checks, ConflictPolicy, SanitizerConflict, SanitizerSet, UbsanMode,
+        UbsanRuntime, COVERAGE_EDGE_FEATURES,
+    },
 };
 
+/// The sanitizers Argus toggles: (sanitizer, environment variable, companion
+/// flags). CFI companions depend on the command, see `cfi_flags`, and UBSan
+/// also gets the flags of its mode.
+const SANITIZERS: &[(&str, &str, &[&str])] = &[
+    ("address", ENABLE_ASAN, &["-U_FORTIFY_SOURCE"]),
+    ("memory", ENABLE_MSAN, &[]),
+    ("undefined", ENABLE_UBSAN, &["-fno-omit-frame-pointer"]),
+    ("thread", ENABLE_TSAN, &["-fno-omit-frame-pointer"]),
+    ("leak", ENABLE_LSAN, &[]),
+    ("hwaddress", ENABLE_HWASAN, &["-fno-omit-frame-pointer"]),
+    ("cfi", ENABLE_CFISAN, &[]),
+    ("safe-stack", ENABLE_SAFESTACK, &[]),
+    ("kcfi", ENABLE_KCFI, &[]),
+];
+
 #[derive(Default)]
 pub struct SanitizerVisitor {
-    use_asan: bool,
-    use_msan: bool,
-    use_ubsan: bool,
-    use_cov: bool,
+    /// The checks of `-fsanitize=` and `-fno-sanitize=`.
+    sanitizers: SanitizerSet,
+    /// The same before the environment overrides.
+    from_command: SanitizerSet,
+    /// The checks of `-fsanitize-recover=` and `-fno-sanitize-recover=`.
+    recover: SanitizerSet,
+    /// The checks of `-fsanitize-trap=` and `-fno-sanitize-trap=`.
+    trap: SanitizerSet,
+    /// SanitizerCoverage features, empty if coverage is off.
+    coverage: Vec<String>,
+    /// The UBSan checks and groups `AFL_USE_UBSAN` enables.
+    ubsan_checks: Vec<String>,
+    ubsan_mode: UbsanMode,
 }
 
 impl SanitizerVisitor {
@@ -17,78 +56,581 @@ impl SanitizerVisitor {
         Self::default()
     }
 
-    fn init(&mut self, options: &mut Vec<CompilerOption>) {
+    fn init(
+        &mut self,
+        context: &VisitorContext,
+        options: &[CompilerOption],
+    ) -> Result<(), ArgusError> {
+        self.set_ubsan_from_config(context)?;
         self.set_sanitizers_from_options(options);
-        self.override_sanitizers_from_env();
+        self.override_sanitizers_from_env(context);
+        Ok(())
     }
 
-    fn set_sanitizers_from_options(&mut self, options: &mut Vec<CompilerOption>) {
-        for sanitizer_options in options.get_options("-fsanitize") {
-            self.use_asan |= sanitizer_options.contains("address");
-            self.use_msan |= sanitizer_options.contains("memory");
-            self.use_ubsan |= sanitizer_options.contains("undefined");
-            self.use_cov |= sanitizer_options.contains("trace-pc-guard");
+    /// Read the check list and the mode of UBSan.
+    fn set_ubsan_from_config(&mut self, context: &VisitorContext) -> Result<(), ArgusError> {
+        let config = &context.config;
+        self.ubsan_checks = match config.get(UBSAN_CHECKS) {
+            Some(spec) => parse_ubsan_checks(spec).map_err(ArgusError::InvalidUbsan)?,
+            None => vec![String::from("undefined")],
+        };
+        if let Some(mode) = config.get(UBSAN_MODE) {
+            self.ubsan_mode = UbsanMode::parse(mode).map_err(ArgusError::InvalidUbsan)?;
         }
+        Ok(())
+    }
+
+    /// Apply `-fsanitize=` and `-fno-sanitize=` in command-line order, and
+    /// the same for `-fsanitize-recover=` and `-fsanitize-trap=`.
+    fn set_sanitizers_from_options(&mut self, options: &[CompilerOption]) {
+        self.sanitizers = SanitizerSet::from_options(options, "-fsanitize=", "-fno-sanitize=");
+        self.from_command = self.sanitizers.clone();
+        self.recover =
+            SanitizerSet::from_options(options, "-fsanitize-recover=", "-fno-sanitize-recover=");
+        self.trap = SanitizerSet::from_options(options, "-fsanitize-trap=", "-fno-sanitize-trap=");
     }
 
-    fn override_sanitizers_from_env(&mut self) {
-        if std::env::var(NOSANITIZER).is_ok() {
-            self.use_asan = false;
-            self.use_msan = false;
-            self.use_ubsan = false;
+    /// `BANDFUZZ_NOSAN` turns off the sanitizers of the command, then each
+    /// `AFL_USE_*` turns on a sanitizer, except for the checks the command
+    /// disables explicitly (e.g. `-fno-sanitize=vptr`).
+    fn override_sanitizers_from_env(&mut self, context: &VisitorContext) {
+        let config = &context.config;
+        if config.is_set(NOSANITIZER) {
+            // every sanitizer of the command, but libFuzzer is not one
+            let libfuzzer: Vec<&str> = ["fuzzer", "fuzzer-no-link"]
+                .into_iter()
+                .filter(|check| self.sanitizers.is_enabled(check))
+                .collect();
+            for name in self.from_command.command_enabled() {
+                self.sanitizers.set(name, false);
+            }
+            for check in libfuzzer {
+                self.sanitizers.set(check, true);
+            }
         }
 
-        self.use_asan |= std::env::var(ENABLE_ASAN).is_ok();
-        self.use_msan |= std::env::var(ENABLE_MSAN).is_ok();
-        self.use_ubsan |= std::env::var(ENABLE_UBSAN).is_ok();
-        self.use_cov |= std::env::var(ENABLE_COVSAN).is_ok();
+        for &(sanitizer, env, _) in SANITIZERS {
+            if !config.is_set(env) {
+                continue;
+            }
+            let names = if sanitizer == "undefined" {
+                self.ubsan_checks.clone()
+            } else {
+                vec![sanitizer.to_string()]
+            };
+            for name in &names {
+                self.sanitizers.set_default(name, true);
+            }
+        }
+    }
+
+    /// Merge the coverage features of the command with the ones of
+    /// `BANDFUZZ_COVERAGE`. `BANDFUZZ_USECOV` alone adds `trace-pc-guard`,
+    /// unless the command already instruments edges.
+    fn set_coverage(
+        &mut self,
+        context: &VisitorContext,
+        options: &Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        for coverage_options in options.get_options("-fsanitize-coverage=") {
+            for value in &coverage_options.values {
+                self.add_coverage(value);
+            }
+        }
+
+        let config = &context.config;
+        if let Some(spec) = config.get(COVERAGE) {
+            for feature in parse_coverage_spec(spec).map_err(ArgusError::InvalidCoverage)? {
+                self.add_coverage(feature);
+            }
+        } else if config.is_set(ENABLE_COVSAN)
+            && !self
+                .coverage
+                .iter()
+                .any(|feature| COVERAGE_EDGE_FEATURES.contains(&feature.as_str()))
+        {
+            self.add_coverage("trace-pc-guard");
+        }
+        check_coverage(&self.coverage).map_err(ArgusError::InvalidCoverage)
+    }
+
+    fn add_coverage(&mut self, feature: &str) {
+        if !self.coverage.iter().any(|f| f == feature) {
+            self.coverage.push(feature.to_string());
+        }
+    }
+
+    /// Disable the UBSan checks the command cannot use, e.g. `vptr` with
+    /// `-fno-rtti`, so that `-fsanitize=undefined` still compiles. Returns the
+    /// excluded checks, and whether the `-fsanitize=` of the command asked for
+    /// them. Only those are reported, the others only with `ARGUS_DEBUG` or
+    /// `ARGUS_EXPLAIN`: `AFL_USE_UBSAN` would get a note for every source file.
+    fn exclude_unsupported_checks(
+        &mut self,
+        context: &VisitorContext,
+        options: &Vec<CompilerOption>,
+    ) -> Vec<(&'static str, bool)> {
+        let compat = context.llvm_compat();
+        let verbose = context.config.is_set(ARGUS_DEBUG) || context.config.is_set(ARGUS_EXPLAIN);
+        let mut excluded = Vec::new();
+        for (check, reason) in unsupported_checks(options, &compat, self.ubsan_mode) {
+            // nothing to do if the command excludes it already
+            if !self.sanitizers.is_enabled(check) || self.from_command.is_disabled(check) {
+                continue;
+            }
+            self.sanitizers.set(check, false);
+            let requested = self.from_command.is_enabled(check);
+            if requested || verbose {
+                let message = format!("excluding `{}`, {}", check, reason);
+                report("note", self.name(), &message);
+            }
+            excluded.push((check, requested));
+        }
+        excluded
+    }
+
+    /// Whether any check of the sanitizer is enabled. UBSan counts as enabled
+    /// with any of its checks, even outside the `undefined` group.
+    fn is_active(&self, sanitizer: &str) -> bool {
+        if sanitizer == "undefined" {
+            self.sanitizers.ubsan_enabled()
+        } else {
+            self.sanitizers.any_enabled(sanitizer)
+        }
+    }
+
+    /// Check the sanitizers against the compatibility matrix in
+    /// `crate::sanitizer`, and resolve conflicts as `ARGUS_SANITIZER_CONFLICT` says.
+    fn resolve_conflicts(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut [CompilerOption],
+    ) -> Result<(), ArgusError> {
+        let policy = ConflictPolicy::from_config(&context.config)?;
+        loop {
+            let flags: Vec<String> = options
+                .iter()
+                .filter(|opt| opt.is_enabled)
+                .map(|opt| opt.name.clone())
+                .collect();
+            let flags: Vec<&str> = flags.iter().map(|flag| flag.as_str()).collect();
+            let Some(conflict) = find_conflicts(&self.sanitizers.enabled_checks(), &flags)
+                .into_iter()
+                .next()
+            else {
+                return Ok(());
+            };
+            if policy == ConflictPolicy::Fail {
+                return Err(ArgusError::SanitizerConflict(conflict));
+            }
+
+            match conflict {
+                SanitizerConflict::Incompatible(first, second) => {
+                    let dropped = loser(first, second);
+                    self.sanitizers.set(dropped, false);
+                    let message = format!("{}, dropping `{}`", conflict, dropped);
+                    report("warn", self.name(), &message);
+                }
+                SanitizerConflict::Requirement { flag, .. } => {
+                    options
+                        .iter_mut()
+                        .filter(|opt| opt.name == flag)
+                        .for_each(|opt| opt.disable());
+                    let message = format!("{}, removing `{}`", conflict, flag);
+                    report("warn", self.name(), &message);
+                }
+            }
+        }
     }
 }
 
-fn toggle_sanitizer(
+fn add_companion_flags(
     options: &mut Vec<CompilerOption>,
-    enable: bool,
-    flag: &str,
     additional_flags: &[&str],
-) {
-    if enable {
-        options.add_or_mix(&CompilerOption::from_arg(flag));
-        for &additional_flag in additional_flags {
-            options.add_or_mix(&CompilerOption::from_arg(additional_flag));
+) -> Result<(), ArgusError> {
+    for &additional_flag in additional_flags {
+        options.add_or_mix(&CompilerOption::from_arg(additional_flag))?;
+    }
+    Ok(())
+}
+
+/// The MSan build profile: origin tracking (`ARGUS_MSAN_ORIGINS`, 2 by
+/// default), use-after-destruction checks, PIE, and for C++ the
+/// MSan-instrumented libc++ installed in `ARGUS_MSAN_LIBCXX`. Uninstrumented
+/// C++ standard libraries cause false positives.
+fn msan_profile(
+    context: &VisitorContext,
+    options: &mut Vec<CompilerOption>,
+) -> Result<(), ArgusError> {
+    let origins = context.config.get(MSAN_ORIGINS).unwrap_or("2");
+    if !["0", "1", "2"].contains(&origins) {
+        return Err(ArgusError::InvalidValue {
+            name: MSAN_ORIGINS,
+            value: origins.to_string(),
+            expected: "0, 1 or 2",
+        });
+    }
+    let has_origins = options.iter().any(|opt| {
+        opt.is_enabled
+            && (opt.name.starts_with("-fsanitize-memory-track-origins")
+                || opt.name == "-fno-sanitize-memory-track-origins")
+    });
+    if !has_origins && origins != "0" {
+        let flag = format!("-fsanitize-memory-track-origins={}", origins);
+        add_companion_flags(options, &[&flag])?;
+    }
+    add_companion_flags(options, &["-fsanitize-memory-use-after-dtor"])?;
+
+    let has_pic = ["-fPIC", "-fpic", "-fPIE", "-fpie"]
+        .iter()
+        .any(|flag| !options.get_options(flag).is_empty());
+    if !has_pic {
+        add_companion_flags(options, &["-fPIE"])?;
+    }
+    if context.mode == CompileMode::LinkExecutable && options.get_options("-no-pie").is_empty() {
+        add_companion_flags(options, &["-pie"])?;
+    }
+
+    if let Some(prefix) = context.config.get(MSAN_LIBCXX) {
+        let is_cxx = context.is_cxx()
+            || options
+                .inputs()
+                .iter()
+                .any(|input| matches!(input.kind, InputKind::Cxx | InputKind::ObjCxx));
+        if is_cxx {
+            use_msan_libcxx(context, options, Path::new(prefix))?;
+        }
+    }
+    Ok(())
+}
+
+/// Compile and link against the libc++ installed in `prefix` instead of the
+/// system C++ standard library. Include paths of another libc++ are replaced.
+fn use_msan_libcxx(
+    context: &VisitorContext,
+    options: &mut Vec<CompilerOption>,
+    prefix: &Path,
+) -> Result<(), ArgusError> {
+    let include = prefix.join("include/c++/v1");
+    let lib = prefix.join("lib");
+    for dir in [&include, &lib] {
+        fs::metadata(dir).map_err(|source| ArgusError::Io {
+            path: dir.clone(),
+            source,
+        })?;
+    }
+    let include = include.to_string_lossy().to_string();
+    let lib = lib.to_string_lossy().to_string();
+
+    for option in options.get_mut_options("-stdlib=") {
+        if !option.contains("libc++") {
+            option.disable();
+        }
+    }
+    for flag in ["-I", "-isystem"] {
+        for option in options.get_mut_options(flag) {
+            if option
+                .values
+                .iter()
+                .any(|dir| Path::new(dir).ends_with("include/c++/v1") && *dir != include)
+            {
+                option.disable();
+            }
+        }
+    }
+    add_companion_flags(options, &["-stdlib=libc++", "-nostdinc++"])?;
+    let include = CompilerOption::new_with_value("-isystem", vec![&include]);
+    if !options.contains_option(&include) {
+        options.push(include);
+    }
+
+    if matches!(
+        context.mode,
+        CompileMode::LinkExecutable | CompileMode::LinkShared
+    ) {
+        let search = CompilerOption::new_with_value("-L", vec![&lib]);
+        if !options.contains_option(&search) {
+            options.push(search);
         }
-    } else {
-        for sanitizer_option in options.get_mut_options("-fsanitize") {
-            sanitizer_option.remove_value(flag.split('=').last().unwrap());
+        add_companion_flags(options, &[&format!("-Wl,-rpath,{}", lib)])?;
+        // libc++ may be built with a separate libc++abi
+        let has_libcxxabi = ["libc++abi.so", "libc++abi.a"]
+            .iter()
+            .any(|name| Path::new(&lib).join(name).exists());
+        if has_libcxxabi {
+            add_companion_flags(options, &["-lc++abi"])?;
         }
     }
+    Ok(())
+}
+
+/// CFI needs LTO and an explicit visibility. Settings the command has already
+/// are kept, e.g. `-flto=thin` or `-fvisibility=default`.
+fn cfi_flags(options: &Vec<CompilerOption>) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if options.get_options("-flto").is_empty() && options.get_options("-flto=").is_empty() {
+        flags.push("-flto");
+    }
+    if options.get_options("-fvisibility=").is_empty() {
+        flags.push("-fvisibility=hidden");
+    }
+    flags
 }
 
 impl OptionVisitor for SanitizerVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        self.init(options);
-
-        toggle_sanitizer(
-            options,
-            self.use_asan,
-            "-fsanitize=address",
-            &["-U_FORTIFY_SOURCE"],
+    fn name(&self) -> &'static str {
+        "SanitizerVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        self.init(context, options)?;
+        self.exclude_unsupported_checks(context, options);
+        self.resolve_conflicts(context, options)?;
+        self.set_coverage(context, options)?;
+
+        for &(sanitizer, _, additional_flags) in SANITIZERS {
+            if self.is_active(sanitizer) {
+                add_companion_flags(options, additional_flags)?;
+            }
+        }
+        if self.sanitizers.ubsan_enabled() {
+            // the checks the command names keep their state
+            if self.ubsan_mode.runtime == UbsanRuntime::Trap {
+                self.trap.set_default("all", true);
+            }
+            self.recover.set_default("all", self.ubsan_mode.recover);
+            add_companion_flags(options, &self.ubsan_mode.runtime_flags())?;
+        }
+        if self.sanitizers.any_enabled("memory") {
+            msan_profile(context, options)?;
+        }
+        if self.sanitizers.any_enabled("cfi") {
+            let cfi_flags = cfi_flags(options);
+            add_companion_flags(options, &cfi_flags)?;
+        }
+
+        // the values of the command stay as they are, Argus only appends
+        options.extend(self.sanitizers.changes("-fsanitize=", "-fno-sanitize="));
+        options.extend(
+            self.recover
+                .changes("-fsanitize-recover=", "-fno-sanitize-recover="),
         );
-        toggle_sanitizer(options, self.use_msan, "-fsanitize=memory", &[]);
-        toggle_sanitizer(
-            options,
-            self.use_ubsan,
-            "-fsanitize=undefined",
+        options.extend(self.trap.changes("-fsanitize-trap=", "-fno-sanitize-trap="));
+
+        for feature in &self.coverage {
+            let flag = format!("-fsanitize-coverage={}", feature);
+            if !options
+                .get_options("-fsanitize-coverage=")
+                .iter()
+                .any(|coverage_options| coverage_options.contains(feature))
+            {
+                add_companion_flags(options, &[&flag])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{context::Config, llvm::Toolchain, option_table::parse_args};
+
+    fn context(vars: &[(&str, &str)], major_version: u32) -> VisitorContext {
+        VisitorContext {
+            config: Config::from_vars(
+                vars.iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string())),
+            ),
+            program_name: String::from("argus"),
+            toolchain: Toolchain {
+                llvm_config: PathBuf::from("llvm-config"),
+                bindir: PathBuf::new(),
+                libdir: PathBuf::new(),
+                major_version,
+                cc: None,
+                cxx: None,
+            },
+            object_search_paths: Vec::new(),
+            mode: CompileMode::CompileOnly,
+        }
+    }
+
+    fn visit(vars: &[(&str, &str)], args: &[&str]) -> Result<Vec<String>, ArgusError> {
+        let context = context(vars, 17);
+        let mut options = parse_args(args);
+        SanitizerVisitor::new().visit(&context, &mut options)?;
+        Ok(options.iter().flat_map(|option| option.to_args()).collect())
+    }
+
+    #[test]
+    fn cfi_adds_lto_and_visibility_unless_given() {
+        let args = visit(&[(ENABLE_CFISAN, "1")], &["-c", "x.cc"]).unwrap();
+        assert!(args.contains(&String::from("-fsanitize=cfi")));
+        assert!(args.contains(&String::from("-flto")));
+        assert!(args.contains(&String::from("-fvisibility=hidden")));
+
+        let args = visit(
+            &[(ENABLE_CFISAN, "1")],
+            &["-flto=thin", "-fvisibility=default", "-c", "x.cc"],
+        )
+        .unwrap();
+        assert!(!args.contains(&String::from("-flto")));
+        assert!(!args.contains(&String::from("-fvisibility=hidden")));
+    }
+
+    #[test]
+    fn nosan_disables_the_new_sanitizers() {
+        let args = visit(
+            &[(NOSANITIZER, "1")],
+            &["-fsanitize=thread", "-fsanitize=safe-stack", "-c", "x.c"],
+        )
+        .unwrap();
+        assert!(args.ends_with(&[String::from("-fno-sanitize=thread,safe-stack")]));
+
+        let args = visit(&[(NOSANITIZER, "1"), (ENABLE_TSAN, "1")], &["-c", "x.c"]).unwrap();
+        assert!(args.contains(&String::from("-fsanitize=thread")));
+        assert!(args.contains(&String::from("-fno-omit-frame-pointer")));
+    }
+
+    fn exclude(
+        vars: &[(&str, &str)],
+        major_version: u32,
+        args: &[&str],
+    ) -> (SanitizerVisitor, Vec<(&'static str, bool)>) {
+        let context = context(vars, major_version);
+        let options = parse_args(args);
+        let mut visitor = SanitizerVisitor::new();
+        visitor.init(&context, &options).unwrap();
+        let excluded = visitor.exclude_unsupported_checks(&context, &options);
+        (visitor, excluded)
+    }
+
+    #[test]
+    fn vptr_is_excluded_without_rtti() {
+        let (visitor, excluded) = exclude(
+            &[],
+            17,
+            &["-fsanitize=undefined", "-fno-rtti", "-c", "x.cc"],
+        );
+        assert_eq!(excluded, [("vptr", true)]);
+        assert!(!visitor.sanitizers.is_enabled("vptr"));
+        assert!(visitor.sanitizers.is_enabled("alignment"));
+    }
+
+    #[test]
+    fn function_is_excluded_for_c_sources_before_llvm_17() {
+        let (visitor, excluded) = exclude(&[], 16, &["-fsanitize=function", "-c", "x.c"]);
+        assert_eq!(excluded, [("function", true)]);
+        assert!(!visitor.sanitizers.is_enabled("function"));
+        let (_, excluded) = exclude(&[], 17, &["-fsanitize=function", "-c", "x.c"]);
+        assert!(excluded.is_empty());
+    }
+
+    #[test]
+    fn exclusions_of_the_environment_are_not_reported() {
+        let (visitor, excluded) = exclude(&[(ENABLE_UBSAN, "1")], 16, &["-c", "x.c"]);
+        assert!(excluded.contains(&("function", false)));
+        assert!(excluded.iter().all(|&(_, requested)| !requested));
+        assert!(!visitor.sanitizers.is_enabled("function"));
+    }
+
+    #[test]
+    fn checks_the_command_excludes_are_left_alone() {
+        let (visitor, excluded) = exclude(
+            &[(ENABLE_UBSAN, "1")],
+            16,
+            &["-fno-sanitize=function", "-c", "x.c"],
+        );
+        assert!(!excluded.iter().any(|&(check, _)| check == "function"));
+        assert!(visitor.sanitizers.is_disabled("function"));
+    }
+
+    #[test]
+    fn nosan_disables_every_sanitizer_but_libfuzzer() {
+        let (visitor, _) = exclude(
+            &[(NOSANITIZER, "1")],
+            17,
             &[
-                "-fno-sanitize-recover=all",
-                "-fsanitize-undefined-trap-on-error",
-                "-fno-omit-frame-pointer",
+                "-fsanitize=address,integer,nullability,float-divide-by-zero",
+                "-fsanitize=local-bounds,implicit-conversion,fuzzer",
+                "-c",
+                "x.c",
             ],
         );
-        toggle_sanitizer(
-            options,
-            self.use_cov,
-            "-fsanitize-coverage=trace-pc-guard",
-            &[],
+        assert_eq!(visitor.sanitizers.enabled_checks(), ["fuzzer"]);
+        let changes: Vec<Vec<String>> = visitor
+            .sanitizers
+            .changes("-fsanitize=", "-fno-sanitize=")
+            .iter()
+            .map(|option| option.to_args())
+            .collect();
+        // `integer` covers the checks of `implicit-conversion` already
+        assert_eq!(
+            changes,
+            [["-fno-sanitize=address,integer,nullability,float-divide-by-zero,local-bounds"]]
         );
     }
+
+    #[test]
+    fn ubsan_mode_keeps_the_recover_and_trap_values_of_the_command() {
+        let context = context(&[(ENABLE_UBSAN, "1")], 17);
+        let mut options = parse_args(&[
+            "-fsanitize-recover=alignment",
+            "-fno-sanitize-trap=signed-integer-overflow",
+            "-c",
+            "x.cc",
+        ]);
+        SanitizerVisitor::new()
+            .visit(&context, &mut options)
+            .unwrap();
+        let args: Vec<String> = options.iter().flat_map(|option| option.to_args()).collect();
+        assert!(args.starts_with(&[
+            String::from("-fsanitize-recover=alignment"),
+            String::from("-fno-sanitize-trap=signed-integer-overflow"),
+        ]));
+        assert!(args.ends_with(&[
+            String::from("-fno-sanitize-recover=all"),
+            String::from("-fsanitize-recover=alignment"),
+            String::from("-fsanitize-trap=all"),
+            String::from("-fno-sanitize-trap=signed-integer-overflow"),
+        ]));
+    }
+
+    #[test]
+    fn msan_profile_adds_origins_and_pie() {
+        let args = visit(&[(ENABLE_MSAN, "1")], &["-c", "x.c"]).unwrap();
+        for flag in [
+            "-fsanitize-memory-track-origins=2",
+            "-fsanitize-memory-use-after-dtor",
+            "-fPIE",
+        ] {
+            assert!(args.contains(&String::from(flag)), "{} in {:?}", flag, args);
+        }
+
+        // the command and `ARGUS_MSAN_ORIGINS=0` win
+        let args = visit(
+            &[(ENABLE_MSAN, "1"), (MSAN_ORIGINS, "0")],
+            &["-fPIC", "-c", "x.c"],
+        )
+        .unwrap();
+        assert!(!args
+            .iter()
+            .any(|arg| arg.starts_with("-fsanitize-memory-track-origins")));
+        assert!(!args.contains(&String::from("-fPIE")));
+    }
+
+    #[test]
+    fn msan_origins_must_be_a_depth() {
+        assert!(matches!(
+            visit(&[(ENABLE_MSAN, "1"), (MSAN_ORIGINS, "3")], &["-c", "x.c"]),
+            Err(ArgusError::InvalidValue { .. })
+        ));
+    }
 }
@@ -1,4 +1,8 @@
-use crate::compiler_option::{CompilerOption, OptionManagement};
+use crate::{
+    compiler_option::{CompilerOption, OptionManagement},
+    context::VisitorContext,
+    error::ArgusError,
+};
 
 use super::OptionVisitor;
 
@@ -17,11 +21,34 @@ impl XVisitor {
 }
 
 impl OptionVisitor for XVisitor {
-    fn visit(&mut self, options: &mut Vec<CompilerOption>) {
-        // if options contains -x, then apply this visitor
-        if !options.get_options("-x").is_empty() {
-            options.add_option(&CompilerOption::from_arg("-x"));
-            options.add_option(&CompilerOption::from_arg("none"));
+    fn name(&self) -> &'static str {
+        "XVisitor"
+    }
+
+    fn visit(
+        &mut self,
+        _context: &VisitorContext,
+        options: &mut Vec<CompilerOption>,
+    ) -> Result<(), ArgusError> {
+        // only needed if the last -x still sets a language
+        let Some(last_x) = options
+            .iter()
+            .rposition(|opt| opt.is_enabled && opt.name == "-x")
+        else {
+            return Ok(());
+        };
+        if options[last_x].contains("none") {
+            return Ok(());
         }
+
+        // reset the language right after the last input it applies to, so inputs
+        // added by later visitors are detected by their extension again
+        let position = options
+            .inputs()
+            .last()
+            .filter(|input| input.index > last_x)
+            .map_or(last_x + 1, |input| input.index + 1);
+        options.insert(position, CompilerOption::new_with_value("-x", vec!["none"]));
+        Ok(())
     }
 }
@@ -0,0 +1,108 @@
+use std::fmt;
+
+/// Where an option comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionOrigin {
+    /// Position in the original argv (argv[0] is the compiler itself).
+    Argument(usize),
+    /// Read from the response file given at this argv position.
+    ResponseFile { path: String, argument: usize },
+    /// Added by the named visitor.
+    Visitor(&'static str),
+}
+
+/// A change a visitor made to an existing option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionEvent {
+    Disabled {
+        visitor: &'static str,
+    },
+    Enabled {
+        visitor: &'static str,
+    },
+    Modified {
+        visitor: &'static str,
+        before: String,
+        after: String,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct Provenance {
+    /// `None` until the option is registered by the option manager.
+    pub origin: Option<OptionOrigin>,
+    pub events: Vec<OptionEvent>,
+    /// Identifies the option while a visitor runs, see `CompilerOptionManager::visit`.
+    pub(crate) slot: Option<usize>,
+}
+
+/// A copy of an option is a new option, so it does not take over the slot.
+impl Clone for Provenance {
+    fn clone(&self) -> Self {
+        Provenance {
+            origin: self.origin.clone(),
+            events: self.events.clone(),
+            slot: None,
+        }
+    }
+}
+
+/// The difference one visitor made to the command.
+#[derive(Debug, Default)]
+pub struct VisitDiff {
+    pub visitor: &'static str,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<(String, String)>,
+}
+
+impl VisitDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl fmt::Display for OptionOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionOrigin::Argument(index) => write!(f, "argv[{}]", index),
+            OptionOrigin::ResponseFile { path, argument } => {
+                write!(f, "@{} (argv[{}])", path, argument)
+            }
+            OptionOrigin::Visitor(visitor) => write!(f, "{}", visitor),
+        }
+    }
+}
+
+impl fmt::Display for OptionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionEvent::Disabled { visitor } => write!(f, "disabled by {}", visitor),
+            OptionEvent::Enabled { visitor } => write!(f, "enabled by {}", visitor),
+            OptionEvent::Modified {
+                visitor,
+                before,
+                after,
+            } => write!(f, "{} -> {} by {}", before, after, visitor),
+        }
+    }
+}
+
+impl fmt::Display for VisitDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "{}: no changes", self.visitor);
+        }
+        write!(f, "{}:", self.visitor)?;
+        for option in &self.removed {
+            write!(f, "\n  - {}", option)?;
+        }
+        for (before, after) in &self.modified {
+            write!(f, "\n  ~ {} -> {}", before, after)?;
+        }
+        for option in &self.added {
+            write!(f, "\n  + {}", option)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,955 @@
+use std::fmt;
+
+use crate::{
+    compiler_option::{CompilerOption, OptionManagement},
+    context::Config,
+    env::ARGUS_SANITIZER_CONFLICT,
+    error::ArgusError,
+    input_file::InputKind,
+    llvm_compat::LlvmCompat,
+};
+
+/// Pairs of sanitizers (or checks) clang refuses to combine.
+const INCOMPATIBLE: &[(&str, &str)] = &[
+    ("address", "memory"),
+    ("address", "thread"),
+    ("address", "hwaddress"),
+    ("memory", "thread"),
+    ("memory", "hwaddress"),
+    ("memory", "leak"),
+    ("thread", "hwaddress"),
+    ("thread", "leak"),
+    ("safe-stack", "address"),
+    ("safe-stack", "hwaddress"),
+    ("safe-stack", "memory"),
+    ("safe-stack", "thread"),
+    ("safe-stack", "leak"),
+    // `function`, of the `undefined` group, uses the same function prologue
+    ("kcfi", "function"),
+];
+
+/// Which sanitizer is kept when two of them conflict, the first one wins.
+pub const PRECEDENCE: &[&str] = &[
+    "address",
+    "hwaddress",
+    "memory",
+    "thread",
+    "leak",
+    "safe-stack",
+    "kcfi",
+    "function",
+];
+
+/// `(sanitizer, flags that break it, what it needs)`.
+pub const REQUIREMENTS: &[(&str, &[&str], &str)] = &[
+    ("cfi", &["-fno-lto"], "LTO"),
+    (
+        "memory",
+        &["-no-pie", "-nopie", "-fno-pie", "-fno-PIE"],
+        "PIE",
+    ),
+    (
+        "thread",
+        &["-no-pie", "-nopie", "-fno-pie", "-fno-PIE"],
+        "PIE",
+    ),
+];
+
+/// A violation of the compatibility matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanitizerConflict {
+    Incompatible(&'static str, &'static str),
+    /// The sanitizer needs something (`needs`) the flag turns off.
+    Requirement {
+        sanitizer: &'static str,
+        flag: &'static str,
+        needs: &'static str,
+    },
+}
+
+impl fmt::Display for SanitizerConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanitizerConflict::Incompatible(first, second) => write!(
+                f,
+                "sanitizers `{}` and `{}` cannot be combined",
+                first, second
+            ),
+            SanitizerConflict::Requirement {
+                sanitizer,
+                flag,
+                needs,
+            } => write!(
+                f,
+                "sanitizer `{}` requires {}, but the command has `{}`",
+                sanitizer, needs, flag
+            ),
+        }
+    }
+}
+
+/// The conflicts between the enabled sanitizers, which may be checks or
+/// groups (e.g. `undefined` enables `function`). `flags` are the spellings of
+/// the enabled options of the command.
+pub fn find_conflicts(enabled: &[&str], flags: &[&str]) -> Vec<SanitizerConflict> {
+    let enabled: Vec<&str> = enabled.iter().flat_map(|name| expand(name)).collect();
+    let is_enabled = |name: &str| expand(name).iter().any(|check| enabled.contains(check));
+    let mut conflicts: Vec<SanitizerConflict> = INCOMPATIBLE
+        .iter()
+        .filter(|(first, second)| is_enabled(first) && is_enabled(second))
+        .map(|&(first, second)| SanitizerConflict::Incompatible(first, second))
+        .collect();
+    for &(sanitizer, blocking, needs) in REQUIREMENTS {
+        if !is_enabled(sanitizer) {
+            continue;
+        }
+        for &flag in blocking.iter().filter(|flag| flags.contains(flag)) {
+            conflicts.push(SanitizerConflict::Requirement {
+                sanitizer,
+                flag,
+                needs,
+            });
+        }
+    }
+    conflicts
+}
+
+/// Of two incompatible sanitizers, the one `PRECEDENCE` drops.
+pub fn loser(first: &'static str, second: &'static str) -> &'static str {
+    let rank = |sanitizer| {
+        PRECEDENCE
+            .iter()
+            .position(|&other| other == sanitizer)
+            .unwrap_or(PRECEDENCE.len())
+    };
+    if rank(first) <= rank(second) {
+        second
+    } else {
+        first
+    }
+}
+
+/// What to do with conflicting sanitizers, selected by `ARGUS_SANITIZER_CONFLICT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// `fail` (default): stop and explain the conflict.
+    Fail,
+    /// `precedence`: drop the sanitizer that comes later in `PRECEDENCE`, and
+    /// the flags that break a requirement, with a warning.
+    Precedence,
+}
+
+impl ConflictPolicy {
+    pub fn from_config(config: &Config) -> Result<Self, ArgusError> {
+        match config.get(ARGUS_SANITIZER_CONFLICT) {
+            None | Some("fail") => Ok(ConflictPolicy::Fail),
+            Some("precedence") => Ok(ConflictPolicy::Precedence),
+            Some(value) => Err(ArgusError::InvalidValue {
+                name: ARGUS_SANITIZER_CONFLICT,
+                value: value.to_string(),
+                expected: "fail or precedence",
+            }),
+        }
+    }
+}
+
+/// The SanitizerCoverage features accepted in a coverage spec.
+pub const COVERAGE_FEATURES: &[&str] = &[
+    "trace-pc-guard",
+    "trace-pc",
+    "inline-8bit-counters",
+    "inline-bool-flag",
+    "pc-table",
+    "trace-cmp",
+    "trace-div",
+    "trace-gep",
+    "trace-loads",
+    "trace-stores",
+    "stack-depth",
+    "indirect-calls",
+    "no-prune",
+    "func",
+    "bb",
+    "edge",
+];
+
+/// Features that instrument edges, at least one is needed for the others.
+pub const COVERAGE_EDGE_FEATURES: &[&str] = &[
+    "trace-pc-guard",
+    "trace-pc",
+    "inline-8bit-counters",
+    "inline-bool-flag",
+];
+
+/// Groups of features of which at most one can be used.
+const COVERAGE_EXCLUSIVE: &[&[&str]] = &[&["func", "bb", "edge"], &["trace-pc", "trace-pc-guard"]];
+
+/// `(feature, one of these is needed)`.
+const COVERAGE_REQUIREMENTS: &[(&str, &[&str])] = &[(
+    "pc-table",
+    &["trace-pc-guard", "inline-8bit-counters", "inline-bool-flag"],
+)];
+
+/// An invalid SanitizerCoverage configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoverageProblem {
+    Unknown(String),
+    Exclusive(String, String),
+    Requires {
+        feature: &'static str,
+        one_of: &'static [&'static str],
+    },
+}
+
+impl fmt::Display for CoverageProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoverageProblem::Unknown(feature) => write!(
+                f,
+                "unknown coverage feature `{}`, expected one of {}",
+                feature,
+                COVERAGE_FEATURES.join(", ")
+            ),
+            CoverageProblem::Exclusive(first, second) => write!(
+                f,
+                "coverage features `{}` and `{}` cannot be combined",
+                first, second
+            ),
+            CoverageProblem::Requires { feature, one_of } => write!(
+                f,
+                "coverage feature `{}` requires one of {}",
+                feature,
+                one_of.join(", ")
+            ),
+        }
+    }
+}
+
+/// Parse a comma separated coverage spec, e.g. `inline-8bit-counters,pc-table,trace-cmp`.
+pub fn parse_coverage_spec(spec: &str) -> Result<Vec<&'static str>, CoverageProblem> {
+    let mut features = Vec::new();
+    for feature in spec.split(',').map(|f| f.trim()).filter(|f| !f.is_empty()) {
+        let known = COVERAGE_FEATURES
+            .iter()
+            .find(|&&known| known == feature)
+            .ok_or_else(|| CoverageProblem::Unknown(feature.to_string()))?;
+        if !features.contains(known) {
+            features.push(*known);
+        }
+    }
+    Ok(features)
+}
+
+/// Check a set of coverage features for incompatible combinations. Features
+/// Argus does not know are left to clang.
+pub fn check_coverage(features: &[String]) -> Result<(), CoverageProblem> {
+    let has = |feature: &str| features.iter().any(|f| f == feature);
+    for group in COVERAGE_EXCLUSIVE {
+        let used: Vec<&&str> = group.iter().filter(|feature| has(feature)).collect();
+        if let [first, second, ..] = used.as_slice() {
+            return Err(CoverageProblem::Exclusive(
+                first.to_string(),
+                second.to_string(),
+            ));
+        }
+    }
+    for &(feature, one_of) in COVERAGE_REQUIREMENTS {
+        if has(feature) && !one_of.iter().any(|needed| has(needed)) {
+            return Err(CoverageProblem::Requires { feature, one_of });
+        }
+    }
+    Ok(())
+}
+
+/// Every check Argus knows, in the order flags are emitted. Sanitizers that
+/// are not groups are checks of their own.
+const CHECKS: &[&str] = &[
+    "address",
+    "hwaddress",
+    "memory",
+    "thread",
+    "leak",
+    "safe-stack",
+    "kcfi",
+    "fuzzer",
+    "fuzzer-no-link",
+    "dataflow",
+    // UndefinedBehaviorSanitizer
+    "alignment",
+    "bool",
+    "builtin",
+    "array-bounds",
+    "local-bounds",
+    "enum",
+    "float-cast-overflow",
+    "float-divide-by-zero",
+    "function",
+    "implicit-unsigned-integer-truncation",
+    "implicit-signed-integer-truncation",
+    "implicit-integer-sign-change",
+    "integer-divide-by-zero",
+    "nonnull-attribute",
+    "null",
+    "nullability-arg",
+    "nullability-assign",
+    "nullability-return",
+    "object-size",
+    "pointer-overflow",
+    "return",
+    "returns-nonnull-attribute",
+    "shift-base",
+    "shift-exponent",
+    "unsigned-shift-base",
+    "signed-integer-overflow",
+    "unsigned-integer-overflow",
+    "unreachable",
+    "vla-bound",
+    "vptr",
+    // Control Flow Integrity
+    "cfi-cast-strict",
+    "cfi-derived-cast",
+    "cfi-icall",
+    "cfi-mfcall",
+    "cfi-nvcall",
+    "cfi-unrelated-cast",
+    "cfi-vcall",
+];
+
+/// Sanitizer groups and their checks, as clang defines them. `all` is every
+/// check in `CHECKS`.
+const GROUPS: &[(&str, &[&str])] = &[
+    (
+        "undefined",
+        &[
+            "alignment",
+            "bool",
+            "builtin",
+            "array-bounds",
+            "enum",
+            "float-cast-overflow",
+            "function",
+            "integer-divide-by-zero",
+            "nonnull-attribute",
+            "null",
+            "object-size",
+            "pointer-overflow",
+            "return",
+            "returns-nonnull-attribute",
+            "shift-base",
+            "shift-exponent",
+            "signed-integer-overflow",
+            "unreachable",
+            "vla-bound",
+            "vptr",
+        ],
+    ),
+    (
+        "integer",
+        &[
+            "implicit-unsigned-integer-truncation",
+            "implicit-signed-integer-truncation",
+            "implicit-integer-sign-change",
+            "integer-divide-by-zero",
+            "shift-base",
+            "shift-exponent",
+            "unsigned-shift-base",
+            "signed-integer-overflow",
+            "unsigned-integer-overflow",
+        ],
+    ),
+    (
+        "cfi",
+        &[
+            "cfi-derived-cast",
+            "cfi-icall",
+            "cfi-mfcall",
+            "cfi-nvcall",
+            "cfi-unrelated-cast",
+            "cfi-vcall",
+        ],
+    ),
+    (
+        "implicit-conversion",
+        &[
+            "implicit-unsigned-integer-truncation",
+            "implicit-signed-integer-truncation",
+            "implicit-integer-sign-change",
+        ],
+    ),
+    (
+        "nullability",
+        &[
+            "nullability-arg",
+            "nullability-assign",
+            "nullability-return",
+        ],
+    ),
+    (
+        "implicit-integer-truncation",
+        &[
+            "implicit-unsigned-integer-truncation",
+            "implicit-signed-integer-truncation",
+        ],
+    ),
+    ("bounds", &["array-bounds", "local-bounds"]),
+    ("shift", &["shift-base", "shift-exponent"]),
+];
+
+/// Together, the checks of UndefinedBehaviorSanitizer.
+const UBSAN_GROUPS: &[&str] = &[
+    "undefined",
+    "integer",
+    "nullability",
+    "float-divide-by-zero",
+    "local-bounds",
+];
+
+/// Whether the check (or every check of the group) belongs to
+/// UndefinedBehaviorSanitizer.
+pub fn is_ubsan_check(name: &str) -> bool {
+    name != "all"
+        && expand(name).iter().all(|check| {
+            UBSAN_GROUPS
+                .iter()
+                .any(|group| expand(group).contains(check))
+        })
+}
+
+/// How UndefinedBehaviorSanitizer reports an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UbsanRuntime {
+    /// Execute a trap instruction, no runtime and no diagnostic.
+    #[default]
+    Trap,
+    /// Link the minimal runtime, which prints the check and the address.
+    Minimal,
+    /// Link the full runtime, which prints a diagnostic with the source location.
+    Full,
+}
+
+/// `ARGUS_UBSAN_MODE`: the runtime, and whether the program continues after
+/// an error. Traps are always fatal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UbsanMode {
+    pub runtime: UbsanRuntime,
+    pub recover: bool,
+}
+
+impl UbsanMode {
+    const MODES: &'static [&'static str] =
+        &["trap", "minimal", "minimal-recover", "full", "full-recover"];
+
+    /// Parse a mode, e.g. `trap` or `full-recover`.
+    pub fn parse(spec: &str) -> Result<Self, UbsanProblem> {
+        let (runtime, recover) = match spec.trim() {
+            "trap" => (UbsanRuntime::Trap, false),
+            "minimal" => (UbsanRuntime::Minimal, false),
+            "minimal-recover" => (UbsanRuntime::Minimal, true),
+            "full" => (UbsanRuntime::Full, false),
+            "full-recover" => (UbsanRuntime::Full, true),
+            _ => return Err(UbsanProblem::UnknownMode(spec.to_string())),
+        };
+        Ok(UbsanMode { runtime, recover })
+    }
+
+    /// The flags selecting the runtime, besides `-fsanitize-trap=` and
+    /// `-fsanitize-recover=`.
+    pub fn runtime_flags(&self) -> Vec<&'static str> {
+        match self.runtime {
+            UbsanRuntime::Minimal => vec!["-fsanitize-minimal-runtime"],
+            UbsanRuntime::Trap | UbsanRuntime::Full => Vec::new(),
+        }
+    }
+}
+
+/// An invalid UndefinedBehaviorSanitizer configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UbsanProblem {
+    UnknownCheck(String),
+    UnknownMode(String),
+}
+
+impl fmt::Display for UbsanProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UbsanProblem::UnknownCheck(check) => write!(
+                f,
+                "`{}` is not an UndefinedBehaviorSanitizer check or group",
+                check
+            ),
+            UbsanProblem::UnknownMode(mode) => write!(
+                f,
+                "unknown UndefinedBehaviorSanitizer mode `{}`, expected one of {}",
+                mode,
+                UbsanMode::MODES.join(", ")
+            ),
+        }
+    }
+}
+
+/// Parse a comma separated list of UBSan checks and groups, e.g.
+/// `integer,bounds,nullability`.
+pub fn parse_ubsan_checks(spec: &str) -> Result<Vec<String>, UbsanProblem> {
+    let mut checks: Vec<String> = Vec::new();
+    for check in spec.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        if !is_ubsan_check(check) {
+            return Err(UbsanProblem::UnknownCheck(check.to_string()));
+        }
+        if !checks.iter().any(|c| c == check) {
+            checks.push(check.to_string());
+        }
+    }
+    Ok(checks)
+}
+
+/// The checks of a group, or the check itself. Unknown names are checks.
+pub fn expand(name: &str) -> Vec<&str> {
+    if name == "all" {
+        return CHECKS.to_vec();
+    }
+    GROUPS
+        .iter()
+        .find(|(group, _)| *group == name)
+        .map_or_else(|| vec![name], |(_, checks)| checks.to_vec())
+}
+
+/// The checks and groups named by positive and negative flags (e.g.
+/// `-fsanitize=` and `-fno-sanitize=`), in command-line order, followed by
+/// the changes made with `set`. The values of the command are kept as given,
+/// so writing the set back only appends the changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SanitizerSet {
+    /// `(check or group, enabled)`.
+    entries: Vec<(String, bool)>,
+    /// How many entries come from the command.
+    from_command: usize,
+}
+
+impl SanitizerSet {
+    pub fn from_options(options: &[CompilerOption], positive: &str, negative: &str) -> Self {
+        let mut entries = Vec::new();
+        for option in options.iter().filter(|opt| opt.is_enabled) {
+            let enable = if option.name == positive {
+                true
+            } else if option.name == negative {
+                false
+            } else {
+                continue;
+            };
+            entries.extend(option.values.iter().map(|value| (value.clone(), enable)));
+        }
+        SanitizerSet {
+            from_command: entries.len(),
+            entries,
+        }
+    }
+
+    /// Enable or disable a check, or all checks of a group. Earlier changes
+    /// the new one overrides are dropped, and nothing is recorded if the
+    /// checks are in that state already.
+    pub fn set(&mut self, name: &str, enable: bool) {
+        let checks = expand(name);
+        if checks.iter().all(|check| self.is_enabled(check) == enable) {
+            return;
+        }
+        self.drop_changes_to(&checks);
+        if checks.iter().any(|check| self.is_enabled(check) != enable) {
+            self.entries.push((name.to_string(), enable));
+        }
+    }
+
+    /// Put a check, or all checks of a group, in a state the command can
+    /// override: checks the command puts in the other state keep it (e.g.
+    /// `-fsanitize-recover=alignment` with `-fno-sanitize-recover=all`).
+    /// Unlike `set`, checks nothing names are not taken as disabled, since
+    /// the defaults of `-fsanitize-recover=` and `-fsanitize-trap=` depend on
+    /// the check.
+    pub fn set_default(&mut self, name: &str, enable: bool) {
+        let checks = expand(name);
+        let (kept, changed): (Vec<&str>, Vec<&str>) = checks
+            .iter()
+            .partition(|check| self.command_state(check) == Some(!enable));
+        if changed
+            .iter()
+            .all(|check| self.state(check) == Some(enable))
+        {
+            return;
+        }
+        self.drop_changes_to(&checks);
+        if changed
+            .iter()
+            .all(|check| self.state(check) == Some(enable))
+        {
+            return;
+        }
+        self.entries.push((name.to_string(), enable));
+        self.entries
+            .extend(kept.iter().map(|check| (check.to_string(), !enable)));
+    }
+
+    /// Drop the changes a change to these checks overrides.
+    fn drop_changes_to(&mut self, checks: &[&str]) {
+        let mut index = self.from_command;
+        while index < self.entries.len() {
+            if expand(&self.entries[index].0)
+                .iter()
+                .all(|check| checks.contains(check))
+            {
+                self.entries.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// The state the last entry naming the check, or one of its groups, gives.
+    fn state(&self, check: &str) -> Option<bool> {
+        last_state(&self.entries, check)
+    }
+
+    /// The same for the entries of the command only.
+    fn command_state(&self, check: &str) -> Option<bool> {
+        last_state(&self.entries[..self.from_command], check)
+    }
+
+    /// The checks and groups the command enables, in command-line order.
+    pub fn command_enabled(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for (name, enabled) in &self.entries[..self.from_command] {
+            if *enabled && !names.contains(&name.as_str()) {
+                names.push(name.as_str());
+            }
+        }
+        names
+    }
+
+    pub fn is_enabled(&self, check: &str) -> bool {
+        self.state(check) == Some(true)
+    }
+
+    /// Whether the check was named by a negative flag last.
+    pub fn is_disabled(&self, check: &str) -> bool {
+        self.state(check) == Some(false)
+    }
+
+    /// Whether any UndefinedBehaviorSanitizer check is enabled.
+    pub fn ubsan_enabled(&self) -> bool {
+        UBSAN_GROUPS.iter().any(|group| self.any_enabled(group))
+    }
+
+    /// Whether any check of the group (or the check itself) is enabled.
+    pub fn any_enabled(&self, name: &str) -> bool {
+        expand(name).iter().any(|check| self.is_enabled(check))
+    }
+
+    /// The enabled checks, in `CHECKS` order, then the unknown ones.
+    pub fn enabled_checks(&self) -> Vec<&str> {
+        let mut checks: Vec<&str> = CHECKS.to_vec();
+        for (name, _) in &self.entries {
+            for check in expand(name) {
+                if !checks.contains(&check) {
+                    checks.push(check);
+                }
+            }
+        }
+        checks.retain(|check| self.is_enabled(check));
+        checks
+    }
+
+    /// The changes made with `set` as flags to append to the command: each
+    /// run of enabled (disabled) names goes to one positive (negative) flag,
+    /// in the order of the changes.
+    pub fn changes(&self, positive: &str, negative: &str) -> Vec<CompilerOption> {
+        let mut flags: Vec<(bool, Vec<&str>)> = Vec::new();
+        for (name, enabled) in &self.entries[self.from_command..] {
+            match flags.last_mut() {
+                Some((last, names)) if last == enabled => names.push(name),
+                _ => flags.push((*enabled, vec![name])),
+            }
+        }
+        flags
+            .into_iter()
+            .map(|(enabled, names)| {
+                let name = if enabled { positive } else { negative };
+                CompilerOption::new_with_value(name, names)
+            })
+            .collect()
+    }
+}
+
+fn last_state(entries: &[(String, bool)], check: &str) -> Option<bool> {
+    entries
+        .iter()
+        .rev()
+        .find(|(name, _)| expand(name).contains(&check))
+        .map(|&(_, enabled)| enabled)
+}
+
+/// The UBSan checks clang cannot use for this command, with the reason:
+/// `vptr` needs RTTI and the full runtime, and before LLVM 17 `function`
+/// needs RTTI and C++ sources only.
+pub fn unsupported_checks(
+    options: &Vec<CompilerOption>,
+    compat: &LlvmCompat,
+    mode: UbsanMode,
+) -> Vec<(&'static str, &'static str)> {
+    let no_rtti = options
+        .iter()
+        .rev()
+        .find(|opt| opt.is_enabled && (opt.name == "-frtti" || opt.name == "-fno-rtti"))
+        .is_some_and(|opt| opt.name == "-fno-rtti");
+    let c_sources = options
+        .inputs()
+        .iter()
+        .any(|input| matches!(input.kind, InputKind::C | InputKind::ObjC));
+    let type_hash = compat.capabilities().function_sanitizer_type_hash;
+
+    let mut unsupported = Vec::new();
+    if no_rtti {
+        unsupported.push(("vptr", "it needs RTTI and the command has -fno-rtti"));
+    } else if mode.runtime == UbsanRuntime::Trap {
+        unsupported.push(("vptr", "it cannot trap and needs the full runtime"));
+    } else if mode.runtime == UbsanRuntime::Minimal {
+        unsupported.push(("vptr", "the minimal runtime does not support it"));
+    }
+    if no_rtti && !type_hash {
+        unsupported.push((
+            "function",
+            "it needs RTTI before LLVM 17 and the command has -fno-rtti",
+        ));
+    } else if c_sources && !type_hash {
+        unsupported.push(("function", "it supports C++ sources only before LLVM 17"));
+    }
+    unsupported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_table::parse_args;
+
+    /// The command with the changes of the set appended, as arguments.
+    fn write(args: &[&str], set: &SanitizerSet, positive: &str, negative: &str) -> Vec<String> {
+        let mut options = parse_args(args);
+        options.extend(set.changes(positive, negative));
+        options.iter().flat_map(|option| option.to_args()).collect()
+    }
+
+    #[test]
+    fn vptr_needs_the_full_runtime() {
+        let options = parse_args(&["-c", "x.cc"]);
+        let compat = LlvmCompat::new(17);
+        let vptr_excluded = |runtime| {
+            let mode = UbsanMode {
+                runtime,
+                recover: false,
+            };
+            unsupported_checks(&options, &compat, mode)
+                .iter()
+                .any(|&(check, _)| check == "vptr")
+        };
+        assert!(vptr_excluded(UbsanRuntime::Trap));
+        assert!(vptr_excluded(UbsanRuntime::Minimal));
+        assert!(!vptr_excluded(UbsanRuntime::Full));
+    }
+
+    #[test]
+    fn all_minus_one_check_round_trips() {
+        let args = ["-fsanitize-trap=all", "-fno-sanitize-trap=vptr"];
+        let options = parse_args(&args);
+        let set = SanitizerSet::from_options(&options, "-fsanitize-trap=", "-fno-sanitize-trap=");
+        assert!(set.is_enabled("address") && set.is_enabled("alignment"));
+        assert!(set.is_disabled("vptr"));
+        assert_eq!(
+            write(&args, &set, "-fsanitize-trap=", "-fno-sanitize-trap="),
+            args
+        );
+
+        let reparsed = parse_args(&write(
+            &args,
+            &set,
+            "-fsanitize-trap=",
+            "-fno-sanitize-trap=",
+        ));
+        assert_eq!(
+            SanitizerSet::from_options(&reparsed, "-fsanitize-trap=", "-fno-sanitize-trap=")
+                .enabled_checks(),
+            set.enabled_checks()
+        );
+    }
+
+    #[test]
+    fn groups_and_unknown_values_are_kept() {
+        let args = ["-fsanitize=undefined,realtime", "-fno-sanitize=vptr"];
+        let options = parse_args(&args);
+        let mut set = SanitizerSet::from_options(&options, "-fsanitize=", "-fno-sanitize=");
+        assert!(set.is_enabled("realtime"));
+        assert!(set.enabled_checks().ends_with(&["realtime"]));
+
+        // no-ops are not written
+        set.set("vptr", false);
+        set.set("alignment", true);
+        set.set("memory", false);
+        assert_eq!(write(&args, &set, "-fsanitize=", "-fno-sanitize="), args);
+
+        set.set("address", true);
+        set.set("function", false);
+        assert_eq!(
+            write(&args, &set, "-fsanitize=", "-fno-sanitize="),
+            [
+                "-fsanitize=undefined,realtime",
+                "-fno-sanitize=vptr",
+                "-fsanitize=address",
+                "-fno-sanitize=function",
+            ]
+        );
+    }
+
+    #[test]
+    fn defaults_keep_the_values_of_the_command() {
+        let args = ["-fsanitize-recover=alignment"];
+        let options = parse_args(&args);
+        let mut set =
+            SanitizerSet::from_options(&options, "-fsanitize-recover=", "-fno-sanitize-recover=");
+        set.set_default("all", false);
+        assert!(set.is_enabled("alignment"));
+        assert!(set.is_disabled("null"));
+        assert_eq!(
+            write(&args, &set, "-fsanitize-recover=", "-fno-sanitize-recover="),
+            [
+                "-fsanitize-recover=alignment",
+                "-fno-sanitize-recover=all",
+                "-fsanitize-recover=alignment",
+            ]
+        );
+
+        // unnamed checks are not taken as disabled
+        let mut set = SanitizerSet::default();
+        set.set_default("all", false);
+        assert_eq!(
+            write(&[], &set, "-fsanitize-recover=", "-fno-sanitize-recover="),
+            ["-fno-sanitize-recover=all"]
+        );
+
+        // nothing to do if the command names every check
+        let args = ["-fsanitize-trap=all", "-fno-sanitize-trap=vptr"];
+        let options = parse_args(&args);
+        let mut set =
+            SanitizerSet::from_options(&options, "-fsanitize-trap=", "-fno-sanitize-trap=");
+        set.set_default("all", true);
+        assert_eq!(
+            write(&args, &set, "-fsanitize-trap=", "-fno-sanitize-trap="),
+            args
+        );
+    }
+
+    #[test]
+    fn changes_keep_their_order() {
+        let mut set = SanitizerSet::default();
+        set.set("integer", false);
+        set.set("undefined", true);
+        set.set("function", false);
+        assert!(set.is_enabled("signed-integer-overflow"));
+        assert!(!set.is_enabled("unsigned-integer-overflow"));
+        assert_eq!(
+            write(&[], &set, "-fsanitize=", "-fno-sanitize="),
+            ["-fsanitize=undefined", "-fno-sanitize=function"]
+        );
+
+        // a later change overrides the earlier ones it covers
+        set.set("address", false);
+        set.set("address", true);
+        set.set("undefined", false);
+        assert_eq!(
+            write(&[], &set, "-fsanitize=", "-fno-sanitize="),
+            ["-fsanitize=address"]
+        );
+    }
+
+    #[test]
+    fn conflict_policy_values() {
+        let policy = |value: &str| {
+            ConflictPolicy::from_config(&Config::from_vars([(
+                ARGUS_SANITIZER_CONFLICT.to_string(),
+                value.to_string(),
+            )]))
+        };
+        assert_eq!(
+            ConflictPolicy::from_config(&Config::default()).unwrap(),
+            ConflictPolicy::Fail
+        );
+        assert_eq!(policy("precedence").unwrap(), ConflictPolicy::Precedence);
+        assert!(matches!(
+            policy("precedance"),
+            Err(ArgusError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn safe_stack_conflicts_with_runtime_sanitizers() {
+        for other in ["address", "hwaddress", "memory", "thread", "leak"] {
+            assert_eq!(
+                find_conflicts(&["safe-stack", other], &[]),
+                [SanitizerConflict::Incompatible("safe-stack", other)]
+            );
+            assert_eq!(loser("safe-stack", other), "safe-stack");
+        }
+    }
+
+    #[test]
+    fn kcfi_conflicts_with_the_function_check_of_undefined() {
+        assert_eq!(
+            find_conflicts(&["kcfi", "undefined"], &[]),
+            [SanitizerConflict::Incompatible("kcfi", "function")]
+        );
+        assert_eq!(loser("kcfi", "function"), "function");
+        assert!(find_conflicts(&["kcfi", "signed-integer-overflow"], &[]).is_empty());
+    }
+
+    #[test]
+    fn requirements_apply_to_groups() {
+        assert_eq!(
+            find_conflicts(&["cfi-icall"], &["-fno-lto"]),
+            [SanitizerConflict::Requirement {
+                sanitizer: "cfi",
+                flag: "-fno-lto",
+                needs: "LTO",
+            }]
+        );
+    }
+
+    #[test]
+    fn coverage_specs_are_checked_against_the_known_features() {
+        assert_eq!(
+            parse_coverage_spec(" inline-8bit-counters,pc-table,,inline-8bit-counters "),
+            Ok(vec!["inline-8bit-counters", "pc-table"])
+        );
+        assert_eq!(
+            parse_coverage_spec("trace-pc-gaurd"),
+            Err(CoverageProblem::Unknown(String::from("trace-pc-gaurd")))
+        );
+    }
+
+    #[test]
+    fn coverage_combinations() {
+        let features = |list: &[&str]| list.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            check_coverage(&features(&["trace-pc", "trace-pc-guard"])),
+            Err(CoverageProblem::Exclusive(
+                String::from("trace-pc"),
+                String::from("trace-pc-guard")
+            ))
+        );
+        assert!(matches!(
+            check_coverage(&features(&["trace-pc", "pc-table"])),
+            Err(CoverageProblem::Requires {
+                feature: "pc-table",
+                ..
+            })
+        ));
+        assert_eq!(
+            check_coverage(&features(&["inline-bool-flag", "pc-table", "bb", "custom"])),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    embedded::{fnv1a, write},
+    error::ArgusError,
+    llvm::cache_dir,
+};
+
+/// Source file globs and function names for a sanitizer special case list,
+/// the format of `-fsanitize-ignorelist=` and of the coverage allow and
+/// ignore lists.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecialCaseList {
+    pub sources: Vec<ListEntry>,
+    pub functions: Vec<ListEntry>,
+}
+
+/// A glob together with the `[section]` it was listed under, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub section: Option<String>,
+    pub glob: String,
+}
+
+impl SpecialCaseList {
+    /// Parse a comma separated list of entries. `fun:<name>` entries are
+    /// functions, anything else (with or without `src:`) is a source glob.
+    /// A `[section]` entry, e.g. `[address]`, applies to the entries after it.
+    pub fn parse(spec: &str) -> Self {
+        let mut list = SpecialCaseList::default();
+        let mut section = None;
+        for entry in spec.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            if let Some(name) = entry.strip_prefix('[').and_then(|e| e.strip_suffix(']')) {
+                section = Some(name.to_string());
+            } else if let Some(function) = entry.strip_prefix("fun:") {
+                list.functions.push(ListEntry {
+                    section: section.clone(),
+                    glob: function.to_string(),
+                });
+            } else {
+                let source = entry.strip_prefix("src:").unwrap_or(entry);
+                list.sources.push(ListEntry {
+                    section: section.clone(),
+                    glob: source.to_string(),
+                });
+            }
+        }
+        list
+    }
+
+    /// Whether a source file, as given on the command line, matches a glob.
+    pub fn matches_source(&self, path: &str) -> bool {
+        self.sources
+            .iter()
+            .any(|entry| glob_match(&entry.glob, path))
+    }
+
+    /// An allowlist instruments a function only if both its source file and
+    /// its name match, so an empty side of a section matches everything.
+    pub fn to_allowlist(&self) -> String {
+        let mut content = String::from("# Generated by Argus.\n");
+        for section in self.sections() {
+            let sources = globs(&self.sources, section);
+            let functions = globs(&self.functions, section);
+            render(
+                &mut content,
+                section,
+                if sources.is_empty() { &["*"] } else { &sources },
+                if functions.is_empty() {
+                    &["*"]
+                } else {
+                    &functions
+                },
+            );
+        }
+        content
+    }
+
+    /// An ignorelist skips a function if its source file or its name match.
+    pub fn to_ignorelist(&self) -> String {
+        let mut content = String::from("# Generated by Argus.\n");
+        for section in self.sections() {
+            render(
+                &mut content,
+                section,
+                &globs(&self.sources, section),
+                &globs(&self.functions, section),
+            );
+        }
+        content
+    }
+
+    /// The sections in the order they are first listed in.
+    fn sections(&self) -> Vec<Option<&str>> {
+        let mut sections = Vec::new();
+        for entry in self.sources.iter().chain(&self.functions) {
+            let section = entry.section.as_deref();
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        // entries outside of a section come first, before any header
+        sections.sort_by_key(|section| section.is_some());
+        sections
+    }
+}
+
+fn globs<'a>(entries: &'a [ListEntry], section: Option<&str>) -> Vec<&'a str> {
+    entries
+        .iter()
+        .filter(|entry| entry.section.as_deref() == section)
+        .map(|entry| entry.glob.as_str())
+        .collect()
+}
+
+fn render(content: &mut String, section: Option<&str>, sources: &[&str], functions: &[&str]) {
+    if let Some(section) = section {
+        content.push_str(&format!("[{}]\n", section));
+    }
+    for source in sources {
+        content.push_str(&format!("src:{}\n", source));
+    }
+    for function in functions {
+        content.push_str(&format!("fun:{}\n", function));
+    }
+}
+
+/// Match a glob as clang does for special case lists: `*` matches any
+/// sequence, `/` included, `?` any single character and `[...]` a character
+/// class such as `[abc]`, `[a-z]` or `[!a-z]`.
+pub fn glob_match(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // where the last `*` was, and the text position it currently matches up to
+    let mut star = None;
+    while t < text.len() {
+        let step = match glob.get(g) {
+            Some('?') => Some(g + 1),
+            Some('[') => match match_class(&glob[g + 1..], text[t]) {
+                Some((true, length)) => Some(g + 1 + length),
+                Some((false, _)) => None,
+                // an unterminated class is a literal `[`
+                None => (text[t] == '[').then_some(g + 1),
+            },
+            Some(&c) if c != '*' && c == text[t] => Some(g + 1),
+            _ => None,
+        };
+        if let Some(next) = step {
+            g = next;
+            t += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            star = Some((g, t));
+            g += 1;
+        } else if let Some((star_g, star_t)) = star {
+            g = star_g + 1;
+            t = star_t + 1;
+            star = Some((star_g, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the class starting after a `[`. Returns whether it
+/// matched and the length of the class including the closing `]`, or `None`
+/// if the class is not terminated.
+fn match_class(class: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some('!' | '^'));
+    let mut i = usize::from(negated);
+    let mut matched = false;
+    // a `]` right after the `[` (or the negation) is part of the class
+    let mut first = true;
+    while i < class.len() {
+        if class[i] == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if i + 2 < class.len() && class[i + 1] == '-' && class[i + 2] != ']' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Write a list to `<cache dir>/lists/<hash>.txt`, unless it exists already.
+/// Lists are stored by content, so different settings never share a file.
+pub fn write_list(content: &str) -> Result<PathBuf, ArgusError> {
+    let dir = cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("argus"))
+        .join("lists");
+    let name = format!("{:016x}.txt", fnv1a(content.as_bytes()));
+    let path = dir.join(&name);
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() == content.len() as u64) {
+        return Ok(path);
+    }
+    write(&dir, &name, content.as_bytes()).map_err(|source| ArgusError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(section: Option<&str>, glob: &str) -> ListEntry {
+        ListEntry {
+            section: section.map(String::from),
+            glob: glob.to_string(),
+        }
+    }
+
+    #[test]
+    fn star_matches_any_sequence() {
+        assert!(glob_match("*.c", "src/a.c"));
+        assert!(glob_match(
+            "*/third_party/*",
+            "lib/third_party/zlib/inflate.c"
+        ));
+        assert!(glob_match("src/*", "src/"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("*.c", "a.cc"));
+        assert!(!glob_match("src/*", "lib/src/a.c"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_match("a?.c", "ab.c"));
+        assert!(glob_match("a?.c", "a/.c"));
+        assert!(!glob_match("a?.c", "a.c"));
+        assert!(!glob_match("a?.c", "abc.c"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(glob_match("[ab].c", "a.c"));
+        assert!(!glob_match("[ab].c", "c.c"));
+        assert!(glob_match("file[0-9].c", "file7.c"));
+        assert!(!glob_match("file[0-9].c", "filex.c"));
+        assert!(glob_match("[!a-c]*", "d.c"));
+        assert!(glob_match("[^a-c]*", "d.c"));
+        assert!(!glob_match("[!a-c]*", "b.c"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("*[-x]", "a-"));
+        // an unterminated class is literal
+        assert!(glob_match("a[b", "a[b"));
+        assert!(!glob_match("a[b", "ab"));
+    }
+
+    #[test]
+    fn prefixes_and_sections() {
+        let list =
+            SpecialCaseList::parse("src/*.c, src:lib/*, fun:parse_*, [address], fun:main, *.h");
+        assert_eq!(
+            list.sources,
+            [
+                entry(None, "src/*.c"),
+                entry(None, "lib/*"),
+                entry(Some("address"), "*.h")
+            ]
+        );
+        assert_eq!(
+            list.functions,
+            [entry(None, "parse_*"), entry(Some("address"), "main")]
+        );
+        assert!(list.matches_source("lib/x.c"));
+        assert!(list.matches_source("include/x.h"));
+        assert!(!list.matches_source("include/x.hpp"));
+        assert_eq!(
+            list.to_ignorelist(),
+            "# Generated by Argus.\n\
+             src:src/*.c\nsrc:lib/*\nfun:parse_*\n\
+             [address]\nsrc:*.h\nfun:main\n"
+        );
+        assert_eq!(SpecialCaseList::parse(" , "), SpecialCaseList::default());
+    }
+
+    #[test]
+    fn allowlist_sides_default_to_everything() {
+        assert_eq!(
+            SpecialCaseList::parse("fun:main").to_allowlist(),
+            "# Generated by Argus.\nsrc:*\nfun:main\n"
+        );
+        assert_eq!(
+            SpecialCaseList::parse("[coverage],src/*").to_allowlist(),
+            "# Generated by Argus.\n[coverage]\nsrc:src/*\nfun:*\n"
+        );
+    }
+}
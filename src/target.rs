@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::compiler_option::CompilerOption;
+
+/// The CPU architecture a command compiles for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    /// x86_64 with 32-bit pointers (`-mx32`).
+    X32,
+    I386,
+    Aarch64,
+    Arm,
+    Riscv64,
+    /// An architecture Argus does not know, by its triple name.
+    Other(String),
+}
+
+impl Arch {
+    /// The architecture of the host Argus runs on.
+    pub fn host() -> Self {
+        match std::env::consts::ARCH {
+            "x86" => Arch::I386,
+            arch => Arch::from_triple(arch),
+        }
+    }
+
+    /// Parse the architecture of a triple like `aarch64-linux-gnu`.
+    pub fn from_triple(triple: &str) -> Self {
+        let arch = triple.split('-').next().unwrap_or(triple);
+        match arch {
+            "x86_64" | "amd64" if triple.ends_with("gnux32") => Arch::X32,
+            "x86_64" | "amd64" => Arch::X86_64,
+            "i386" | "i486" | "i586" | "i686" => Arch::I386,
+            "aarch64" | "arm64" => Arch::Aarch64,
+            "riscv64" => Arch::Riscv64,
+            _ if arch.starts_with("arm") || arch.starts_with("thumb") => Arch::Arm,
+            _ => Arch::Other(arch.to_string()),
+        }
+    }
+
+    /// The architecture of a command: `--target=` (or `-target`) replaces the
+    /// host, `-m32`, `-mx32` and `-m64` switch between the x86 variants.
+    pub fn from_options(options: &[CompilerOption]) -> Self {
+        let mut arch = Arch::host();
+        for option in options.iter().filter(|opt| opt.is_enabled) {
+            match option.name.as_str() {
+                "--target=" | "-target" => {
+                    if let Some(triple) = option.values.first() {
+                        arch = Arch::from_triple(triple);
+                    }
+                }
+                "-m32" => {
+                    if matches!(arch, Arch::X86_64 | Arch::X32) {
+                        arch = Arch::I386;
+                    }
+                }
+                "-mx32" => {
+                    if matches!(arch, Arch::X86_64 | Arch::I386) {
+                        arch = Arch::X32;
+                    }
+                }
+                "-m64" => {
+                    if matches!(arch, Arch::I386 | Arch::X32) {
+                        arch = Arch::X86_64;
+                    }
+                }
+                _ => {}
+            }
+        }
+        arch
+    }
+
+    /// The ELF class (`1` for 32-bit, `2` for 64-bit) and machine of objects
+    /// for this architecture, if known.
+    pub fn elf_identity(&self) -> Option<(u8, u16)> {
+        match self {
+            Arch::X86_64 => Some((2, 62)),
+            Arch::X32 => Some((1, 62)),
+            Arch::I386 => Some((1, 3)),
+            Arch::Aarch64 => Some((2, 183)),
+            Arch::Arm => Some((1, 40)),
+            Arch::Riscv64 => Some((2, 243)),
+            Arch::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arch::X86_64 => write!(f, "x86_64"),
+            Arch::X32 => write!(f, "x32"),
+            Arch::I386 => write!(f, "i386"),
+            Arch::Aarch64 => write!(f, "aarch64"),
+            Arch::Arm => write!(f, "arm"),
+            Arch::Riscv64 => write!(f, "riscv64"),
+            Arch::Other(arch) => write!(f, "{}", arch),
+        }
+    }
+}
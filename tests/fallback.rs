@@ -0,0 +1,60 @@
+//! Runs the argus binary against a fake compiler that prints its arguments.
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+fn fake_bin_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("argus-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let clang = dir.join("clang");
+    fs::write(&clang, "#!/bin/sh\necho \"$@\"\n").unwrap();
+    fs::set_permissions(&clang, fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+/// Run argus with only the fake compiler in `PATH`, so there is no `llvm-config`.
+fn run_argus(dir: &Path, envs: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_argus"))
+        .args(["-c", "foo.c"])
+        .env_clear()
+        .env("PATH", dir)
+        .env("HOME", dir)
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn fallback_runs_the_original_command_without_a_toolchain() {
+    let dir = fake_bin_dir("fallback");
+    let output = run_argus(&dir, &[("ARGUS_ON_ERROR", "fallback")]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "-c foo.c");
+}
+
+#[test]
+fn fail_stops_without_a_toolchain() {
+    let dir = fake_bin_dir("fail");
+    let output = run_argus(&dir, &[("ARGUS_ON_ERROR", "fail")]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn fallback_uses_argus_cc() {
+    let dir = fake_bin_dir("fallback-cc");
+    fs::rename(dir.join("clang"), dir.join("my-cc")).unwrap();
+    let output = run_argus(
+        &dir,
+        &[("ARGUS_ON_ERROR", "fallback"), ("ARGUS_CC", "my-cc")],
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "-c foo.c");
+}